@@ -0,0 +1,88 @@
+use super::token::Span;
+
+// Renders the source line a span starts on with the span underlined:
+//
+//  --> 2:7
+//   |
+// 2 | print 1 + "a";
+//   |       ^^^^^^^
+//
+// Spans running over several lines are underlined up to the end of the first.
+// A span that doesn't belong to `source` gives no snippet, and one that cuts
+// through a character is widened to the whole character.
+pub fn render(source: &str, span: Span) -> Option<String> {
+    if span.start > source.len() {
+        return None;
+    }
+    let start = char_boundary(source, span.start);
+    let line_start = source[..start].rfind('\n').map_or(0, |index| index + 1);
+    let line_end = source[start..]
+        .find('\n')
+        .map_or(source.len(), |index| start + index);
+    let end = span.end.clamp(start, line_end);
+    let end = (end..=line_end)
+        .find(|&index| source.is_char_boundary(index))
+        .unwrap_or(line_end);
+
+    let line_number = source[..line_start].matches('\n').count() + 1;
+    let text = source[line_start..line_end].trim_end_matches('\r');
+    let padding: String = source[line_start..start]
+        .chars()
+        .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+        .collect();
+    let underline = "^".repeat(source[start..end].chars().count().max(1));
+    let gutter = " ".repeat(line_number.to_string().len());
+
+    Some(format!(
+        "{gutter}--> {line}:{column}\n{gutter} |\n{line} | {text}\n{gutter} | {padding}{underline}\n",
+        gutter = gutter,
+        line = line_number,
+        column = span.column,
+        text = text,
+        padding = padding,
+        underline = underline
+    ))
+}
+
+fn char_boundary(source: &str, mut index: usize) -> usize {
+    while !source.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn underlines_span() {
+        let source = "var a = 1;\nprint a + \"b\";\n";
+        let span = Span::new(17, 24, 7);
+        assert_eq!(
+            render(source, span).unwrap(),
+            " --> 2:7\n  |\n2 | print a + \"b\";\n  |       ^^^^^^^\n"
+        );
+    }
+
+    #[test]
+    fn empty_span_at_end_of_source() {
+        let source = "print 1";
+        let span = Span::new(7, 7, 8);
+        assert_eq!(
+            render(source, span).unwrap(),
+            " --> 1:8\n  |\n1 | print 1\n  |        ^\n"
+        );
+    }
+
+    #[test]
+    fn spans_that_dont_fit_the_source() {
+        let source = "print \"é\";";
+        assert_eq!(
+            render(source, Span::new(8, 9, 9)).unwrap(),
+            " --> 1:9\n  |\n1 | print \"é\";\n  |        ^\n"
+        );
+        assert_eq!(render(source, Span::new(20, 25, 21)), None);
+    }
+}
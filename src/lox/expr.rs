@@ -1,4 +1,4 @@
-use super::token::Token;
+use super::token::{Span, Token};
 
 #[derive(PartialEq, Clone, Debug)]
 pub enum Expr {
@@ -9,6 +9,48 @@ pub enum Expr {
     Var(Var),
     Assignment(Assignment),
     Unary(Unary),
+    Call(Call),
+    Get(Get),
+    Set(Set),
+    This(This),
+    Super(Super),
+    List(List),
+    Map(Map),
+    Index(Index),
+    SetIndex(SetIndex),
+    Stringify(Stringify),
+    Update(Update),
+    Ternary(Ternary),
+}
+
+impl Expr {
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Grouping(grouping) => grouping.expr.span(),
+            Expr::Binary(binary) => binary.left.span().merge(binary.right.span()),
+            Expr::Literal(literal) => literal.token.span,
+            Expr::Logical(logical) => logical.left.span().merge(logical.right.span()),
+            Expr::Var(var) => var.name.span,
+            Expr::Assignment(assignment) => assignment.name.span.merge(assignment.value.span()),
+            Expr::Unary(unary) => unary.operator.span.merge(unary.expr.span()),
+            Expr::Call(call) => call.callee.span().merge(call.paren.span),
+            Expr::Get(get) => get.object.span().merge(get.name.span),
+            Expr::Set(set) => set.object.span().merge(set.value.span()),
+            Expr::This(this) => this.keyword.span,
+            Expr::Super(super_expr) => super_expr.keyword.span.merge(super_expr.method.span),
+            Expr::List(list) => list.left_bracket.span.merge(list.right_bracket.span),
+            Expr::Map(map) => map.left_brace.span.merge(map.right_brace.span),
+            Expr::Index(index) => index.object.span().merge(index.bracket.span),
+            Expr::SetIndex(set) => set.object.span().merge(set.value.span()),
+            Expr::Stringify(stringify) => stringify.expr.span(),
+            Expr::Ternary(ternary) => ternary.condition.span().merge(ternary.else_branch.span()),
+            Expr::Update(update) => update
+                .target
+                .span()
+                .merge(update.operator.span)
+                .merge(update.value.span()),
+        }
+    }
 }
 
 #[derive(PartialEq, Clone, Debug)]
@@ -44,10 +86,99 @@ pub struct Literal {
 #[derive(PartialEq, Clone, Debug)]
 pub struct Var {
     pub name: Token,
+    pub depth: Option<usize>,
 }
 
 #[derive(PartialEq, Clone, Debug)]
 pub struct Assignment {
     pub name: Token,
     pub value: Box<Expr>,
+    pub depth: Option<usize>,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Call {
+    pub callee: Box<Expr>,
+    pub paren: Token,
+    pub arguments: Vec<Expr>,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Get {
+    pub object: Box<Expr>,
+    pub name: Token,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Set {
+    pub object: Box<Expr>,
+    pub name: Token,
+    pub value: Box<Expr>,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct This {
+    pub keyword: Token,
+    pub depth: Option<usize>,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Super {
+    pub keyword: Token,
+    pub method: Token,
+    pub depth: Option<usize>,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct List {
+    pub left_bracket: Token,
+    pub elements: Vec<Expr>,
+    pub right_bracket: Token,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Map {
+    pub left_brace: Token,
+    pub entries: Vec<(Expr, Expr)>,
+    pub right_brace: Token,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Index {
+    pub object: Box<Expr>,
+    pub index: Box<Expr>,
+    pub bracket: Token,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct SetIndex {
+    pub object: Box<Expr>,
+    pub index: Box<Expr>,
+    pub value: Box<Expr>,
+    pub bracket: Token,
+}
+
+// Converts any value to the string `print` would show, string interpolation
+// is desugared into concatenations of these.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Stringify {
+    pub expr: Box<Expr>,
+}
+
+// `target op= value`, `++target` and `target++`. The target is a Var, Get or
+// Index and is only evaluated once. For `++` and `--` the value is a
+// literal 1.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Update {
+    pub target: Box<Expr>,
+    pub operator: Token,
+    pub value: Box<Expr>,
+    pub postfix: bool,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Ternary {
+    pub condition: Box<Expr>,
+    pub then_branch: Box<Expr>,
+    pub else_branch: Box<Expr>,
 }
@@ -0,0 +1,63 @@
+use super::interpreter::Value;
+use super::native::NativeFunction;
+use std::cell::Cell;
+use std::rc::Rc;
+
+// `passed` counts the assertions that held, for the test runner. A failed
+// assertion is a runtime error with the message as is.
+pub fn natives(passed: &Rc<Cell<usize>>) -> Vec<NativeFunction> {
+    let assert_passed = Rc::clone(passed);
+    let assert_equal_passed = Rc::clone(passed);
+    vec![
+        NativeFunction::new("assert", 2, move |arguments| {
+            if !arguments[0].truthyness() {
+                return Err(arguments[1].to_string());
+            }
+            assert_passed.set(assert_passed.get() + 1);
+            Ok(Value::Nil)
+        }),
+        NativeFunction::new("assertEqual", 2, move |arguments| {
+            let (actual, expected) = (&arguments[0], &arguments[1]);
+            if !same(actual, expected, &mut vec![]) {
+                return Err(format!("Expected {} but got {}.", expected, actual));
+            }
+            assert_equal_passed.set(assert_equal_passed.get() + 1);
+            Ok(Value::Nil)
+        }),
+    ]
+}
+
+// Lists and maps are compared by their contents here, unlike with `==`.
+// `comparing` holds the pairs already being compared further out: running
+// into one again means the values contain themselves, and nothing along that
+// path has differed so far.
+fn same(left: &Value, right: &Value, comparing: &mut Vec<(*const (), *const ())>) -> bool {
+    let pair = match (left, right) {
+        (Value::List(l), Value::List(r)) => {
+            (Rc::as_ptr(l) as *const (), Rc::as_ptr(r) as *const ())
+        }
+        (Value::Map(l), Value::Map(r)) => (Rc::as_ptr(l) as *const (), Rc::as_ptr(r) as *const ()),
+        (left, right) => return left.equals(right),
+    };
+    if comparing.contains(&pair) {
+        return true;
+    }
+    comparing.push(pair);
+    let same = match (left, right) {
+        (Value::List(left), Value::List(right)) => {
+            let (left, right) = (&left.borrow().elements, &right.borrow().elements);
+            left.len() == right.len() && left.iter().zip(right).all(|(l, r)| same(l, r, comparing))
+        }
+        (Value::Map(left), Value::Map(right)) => {
+            let (left, right) = (&left.borrow().entries, &right.borrow().entries);
+            left.len() == right.len()
+                && left
+                    .iter()
+                    .zip(right)
+                    .all(|((lk, lv), (rk, rv))| lk == rk && same(lv, rv, comparing))
+        }
+        _ => unreachable!(),
+    };
+    comparing.pop();
+    same
+}
@@ -0,0 +1,121 @@
+use super::function::Function;
+use super::interpreter::Value;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+pub struct Class {
+    pub name: String,
+    pub superclass: Option<Rc<Class>>,
+    pub methods: HashMap<String, Rc<Function>>,
+}
+
+impl Class {
+    pub fn new(
+        name: String,
+        superclass: Option<Rc<Class>>,
+        methods: HashMap<String, Rc<Function>>,
+    ) -> Self {
+        Self {
+            name,
+            superclass,
+            methods,
+        }
+    }
+
+    pub fn find_method(&self, name: &str) -> Option<Rc<Function>> {
+        match self.methods.get(name) {
+            Some(method) => Some(Rc::clone(method)),
+            None => match &self.superclass {
+                Some(superclass) => superclass.find_method(name),
+                None => None,
+            },
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        match self.find_method("init") {
+            Some(initializer) => initializer.arity(),
+            None => 0,
+        }
+    }
+}
+
+pub struct Instance {
+    pub class: Rc<Class>,
+    fields: HashMap<String, Value>,
+}
+
+impl Instance {
+    pub fn new(class: Rc<Class>) -> Self {
+        Self {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    // Fields shadow methods, methods are bound to the instance they are read from.
+    pub fn get(instance: &Rc<RefCell<Instance>>, name: &str) -> Option<Value> {
+        if let Some(value) = instance.borrow().fields.get(name) {
+            return Some(value.clone());
+        }
+
+        let method = instance.borrow().class.find_method(name)?;
+        let bound = method.bind(Value::Instance(Rc::clone(instance)));
+        Some(Value::Function(Rc::new(bound)))
+    }
+
+    pub fn set(&mut self, name: &str, value: Value) {
+        self.fields.insert(name.into(), value);
+    }
+}
+
+impl PartialEq for Class {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl PartialOrd for Class {
+    fn partial_cmp(&self, _other: &Self) -> Option<Ordering> {
+        None
+    }
+}
+
+impl fmt::Debug for Class {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Class({})", self.name)
+    }
+}
+
+impl fmt::Display for Class {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl PartialEq for Instance {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl PartialOrd for Instance {
+    fn partial_cmp(&self, _other: &Self) -> Option<Ordering> {
+        None
+    }
+}
+
+impl fmt::Debug for Instance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Instance({})", self.class.name)
+    }
+}
+
+impl fmt::Display for Instance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
@@ -21,15 +21,18 @@ impl Environment {
         self.parent_env = Some(enclosing);
     }
 
-    pub fn assign(&mut self, name: &str, value: Value) {
+    pub fn assign(&mut self, name: &str, value: Value) -> Result<(), String> {
         match self.env_values.get(name) {
-            Some(_) => self.define(name, value),
+            Some(_) => {
+                self.define(name, value);
+                Ok(())
+            }
             None => match &self.parent_env {
                 Some(parent_env) => {
                     let env = &mut *parent_env.borrow_mut();
                     env.assign(name, value)
                 }
-                None => println!("Variable not declared with name: {}", name),
+                None => Err(format!("Undefined variable '{}'.", name)),
             },
         }
     }
@@ -49,4 +52,25 @@ impl Environment {
             }
         }
     }
+
+    pub fn get_at(&self, distance: usize, name: &str) -> Option<Value> {
+        if distance == 0 {
+            return self.env_values.get(name).cloned();
+        }
+        match &self.parent_env {
+            None => None,
+            Some(parent_env) => parent_env.borrow().get_at(distance - 1, name),
+        }
+    }
+
+    pub fn assign_at(&mut self, distance: usize, name: &str, value: Value) -> Result<(), String> {
+        if distance == 0 {
+            self.define(name, value);
+            return Ok(());
+        }
+        match &self.parent_env {
+            Some(parent_env) => parent_env.borrow_mut().assign_at(distance - 1, name, value),
+            None => Err(format!("Undefined variable '{}'.", name)),
+        }
+    }
 }
@@ -0,0 +1,240 @@
+use super::class::{Class, Instance};
+use super::interpreter::Value;
+use super::list::new_list;
+use super::native::{string_argument, NativeFunction};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+
+// The directory file natives are restricted to, `None` allows every path.
+pub type Sandbox = Rc<RefCell<Option<PathBuf>>>;
+
+type FileFn = fn(&Path, &str) -> io::Result<Value>;
+
+// Failures are returned as `IOError` instances with a message and the path,
+// so scripts can check them without a try. Arguments of the wrong type are
+// still runtime errors.
+pub fn natives(sandbox: &Sandbox) -> Vec<NativeFunction> {
+    let error_class = Rc::new(Class::new("IOError".into(), None, HashMap::new()));
+    let native = |name, arity, function| {
+        file_native(
+            name,
+            arity,
+            function,
+            Rc::clone(sandbox),
+            Rc::clone(&error_class),
+        )
+    };
+    vec![
+        native("readFile", 1, read_file),
+        native("writeFile", 2, write_file),
+        native("appendFile", 2, append_file),
+        native("fileExists", 1, file_exists),
+        native("listDir", 1, list_dir),
+        native("removeFile", 1, remove_file),
+    ]
+}
+
+fn file_native(
+    name: &str,
+    arity: usize,
+    function: FileFn,
+    sandbox: Sandbox,
+    error_class: Rc<Class>,
+) -> NativeFunction {
+    NativeFunction::new(name, arity, move |arguments| {
+        let path = string_argument(arguments, 0)?;
+        let text = if arity > 1 {
+            string_argument(arguments, 1)?
+        } else {
+            ""
+        };
+        let result = resolve(sandbox.borrow().as_deref(), Path::new(path))
+            .and_then(|resolved| function(&resolved, text));
+        Ok(result.unwrap_or_else(|error| {
+            let mut instance = Instance::new(Rc::clone(&error_class));
+            instance.set("message", Value::String(error.to_string()));
+            instance.set("path", Value::String(path.into()));
+            Value::Instance(Rc::new(RefCell::new(instance)))
+        }))
+    })
+}
+
+// Inside a sandbox relative paths start at the root, and `..` or symlinks
+// can't be used to leave it.
+pub fn resolve(root: Option<&Path>, path: &Path) -> io::Result<PathBuf> {
+    let root = match root {
+        Some(root) => fs::canonicalize(root)?,
+        None => return Ok(path.to_path_buf()),
+    };
+
+    let mut resolved = PathBuf::new();
+    for component in root.join(path).components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            component => resolved.push(component),
+        }
+    }
+
+    let inside = resolved.starts_with(&root)
+        && resolved
+            .ancestors()
+            .take_while(|ancestor| *ancestor != root)
+            .all(|ancestor| link_stays_inside(ancestor, &root));
+    if inside {
+        Ok(resolved)
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("Path '{}' is outside of the sandbox.", path.display()),
+        ))
+    }
+}
+
+// Symlinks, dangling ones included, are only followed when what they point to
+// is inside the root as well.
+fn link_stays_inside(path: &Path, root: &Path) -> bool {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => {
+            fs::canonicalize(path).is_ok_and(|target| target.starts_with(root))
+        }
+        _ => true,
+    }
+}
+
+fn read_file(path: &Path, _text: &str) -> io::Result<Value> {
+    fs::read_to_string(path).map(Value::String)
+}
+
+fn write_file(path: &Path, text: &str) -> io::Result<Value> {
+    fs::write(path, text)?;
+    Ok(Value::Nil)
+}
+
+fn append_file(path: &Path, text: &str) -> io::Result<Value> {
+    let mut file = OpenOptions::new().append(true).create(true).open(path)?;
+    file.write_all(text.as_bytes())?;
+    Ok(Value::Nil)
+}
+
+fn file_exists(path: &Path, _text: &str) -> io::Result<Value> {
+    Ok(Value::Boolean(path.exists()))
+}
+
+// Entry names, sorted so the result doesn't depend on the file system.
+fn list_dir(path: &Path, _text: &str) -> io::Result<Value> {
+    let mut names = vec![];
+    for entry in fs::read_dir(path)? {
+        names.push(entry?.file_name().to_string_lossy().into_owned());
+    }
+    names.sort();
+    Ok(new_list(names.into_iter().map(Value::String).collect()))
+}
+
+fn remove_file(path: &Path, _text: &str) -> io::Result<Value> {
+    fs::remove_file(path)?;
+    Ok(Value::Nil)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lox::interpreter::interpreter::tests::{
+        interpret, interpret_with, runtime_error, temp_dir, variable,
+    };
+    use crate::lox::interpreter::interpreter::Interpreter;
+
+    #[test]
+    fn file_natives() {
+        let dir = temp_dir("files");
+        let source = format!(
+            r#"
+            var path = "{}/notes.txt";
+            var written = writeFile(path, "a");
+            appendFile(path, "b");
+            var content = readFile(path);
+            var exists = fileExists(path);
+            var listing = listDir("{}");
+            removeFile(path);
+            var gone = !fileExists(path);
+            var missing = readFile(path);
+            var missing_path = missing.path;
+        "#,
+            dir.display(),
+            dir.display()
+        );
+        let interpreter = interpret(&source);
+        assert_eq!(variable(&interpreter, "written"), Value::Nil);
+        assert_eq!(
+            variable(&interpreter, "content"),
+            Value::String("ab".into())
+        );
+        assert_eq!(variable(&interpreter, "exists"), Value::Boolean(true));
+        assert_eq!(variable(&interpreter, "listing").to_string(), "[notes.txt]");
+        assert_eq!(variable(&interpreter, "gone"), Value::Boolean(true));
+        assert_eq!(
+            variable(&interpreter, "missing").to_string(),
+            "IOError instance"
+        );
+        assert_eq!(
+            variable(&interpreter, "missing_path"),
+            Value::String(format!("{}/notes.txt", dir.display()))
+        );
+        assert_eq!(
+            runtime_error("writeFile(\"x\", 1);").message,
+            "Expected a string as the second argument."
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn sandbox_keeps_files_inside_the_root() {
+        let dir = temp_dir("sandbox");
+        let mut interpreter = Interpreter::new();
+        interpreter.sandbox(dir.clone());
+        let source = r#"
+            writeFile("inside.txt", "ok");
+            var inside = readFile("inside.txt");
+            var escaped = readFile("../inside.txt").message;
+            var absolute = fileExists("/etc");
+        "#;
+        let interpreter = interpret_with(interpreter, source);
+        assert_eq!(variable(&interpreter, "inside"), Value::String("ok".into()));
+        assert_eq!(
+            variable(&interpreter, "escaped"),
+            Value::String("Path '../inside.txt' is outside of the sandbox.".into())
+        );
+        assert_eq!(
+            variable(&interpreter, "absolute").to_string(),
+            "IOError instance"
+        );
+        assert!(dir.join("inside.txt").exists());
+
+        let outside = temp_dir("sandbox-outside");
+        std::os::unix::fs::symlink(outside.join("escaped.txt"), dir.join("dangling")).unwrap();
+        std::os::unix::fs::symlink(&outside, dir.join("linked")).unwrap();
+        let source = r#"
+            var dangling = writeFile("dangling", "pwned");
+            var linked = writeFile("linked/file.txt", "pwned");
+        "#;
+        let interpreter = interpret_with(interpreter, source);
+        assert_eq!(
+            variable(&interpreter, "dangling").to_string(),
+            "IOError instance"
+        );
+        assert_eq!(
+            variable(&interpreter, "linked").to_string(),
+            "IOError instance"
+        );
+        assert!(!outside.join("escaped.txt").exists());
+        assert!(!outside.join("file.txt").exists());
+        std::fs::remove_dir_all(outside).unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,99 @@
+use super::environment::Environment;
+use super::interpreter::Value;
+use crate::lox::stmt;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::fmt;
+use std::path::Path;
+use std::rc::Rc;
+
+pub struct Function {
+    pub declaration: Rc<stmt::Function>,
+    pub closure: Rc<RefCell<Environment>>,
+    // The top level of the module the function was declared in, where its
+    // global variables are looked up.
+    pub globals: Rc<RefCell<Environment>>,
+    // The imported file it was declared in, `None` for the script being run.
+    pub file: Option<Rc<Path>>,
+    pub is_initializer: bool,
+}
+
+impl Function {
+    pub fn new(
+        declaration: Rc<stmt::Function>,
+        closure: Rc<RefCell<Environment>>,
+        globals: Rc<RefCell<Environment>>,
+        file: Option<Rc<Path>>,
+    ) -> Self {
+        Self {
+            declaration,
+            closure,
+            globals,
+            file,
+            is_initializer: false,
+        }
+    }
+
+    pub fn method(
+        declaration: Rc<stmt::Function>,
+        closure: Rc<RefCell<Environment>>,
+        globals: Rc<RefCell<Environment>>,
+        file: Option<Rc<Path>>,
+    ) -> Self {
+        let is_initializer = declaration.name.lexeme == "init";
+        Self {
+            declaration,
+            closure,
+            globals,
+            file,
+            is_initializer,
+        }
+    }
+
+    pub fn bind(&self, instance: Value) -> Self {
+        let mut env = Environment::new();
+        env.enclose(Rc::clone(&self.closure));
+        env.define("this", instance);
+        Self {
+            declaration: Rc::clone(&self.declaration),
+            closure: Rc::new(RefCell::new(env)),
+            globals: Rc::clone(&self.globals),
+            file: self.file.clone(),
+            is_initializer: self.is_initializer,
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.declaration.name.lexeme
+    }
+}
+
+// Two functions are only equal when they are the same object, the closure
+// environment can point back to the function itself so it can't be compared.
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl PartialOrd for Function {
+    fn partial_cmp(&self, _other: &Self) -> Option<Ordering> {
+        None
+    }
+}
+
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Function({})", self.name())
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<fn {}>", self.name())
+    }
+}
@@ -0,0 +1,102 @@
+use super::interpreter::Value;
+use super::native::NativeFunction;
+use std::cell::RefCell;
+use std::io::{self, BufRead, BufReader};
+use std::rc::Rc;
+
+// Standard input unless the interpreter was given another source.
+pub type Input = Rc<RefCell<Box<dyn BufRead>>>;
+
+thread_local! {
+    // One buffer for every interpreter reading standard input, so input one
+    // of them read ahead isn't lost to the next (`rlox test` runs each file
+    // in a fresh interpreter).
+    static STDIN: Input = Rc::new(RefCell::new(Box::new(BufReader::new(io::stdin()))));
+}
+
+pub fn stdin() -> Input {
+    STDIN.with(Rc::clone)
+}
+
+pub fn natives(input: &Input) -> Vec<NativeFunction> {
+    let line_input = Rc::clone(input);
+    let all_input = Rc::clone(input);
+    vec![
+        NativeFunction::new("readLine", 0, move |_| {
+            read_line(&mut **line_input.borrow_mut())
+        }),
+        NativeFunction::new("readAll", 0, move |_| {
+            read_all(&mut **all_input.borrow_mut())
+        }),
+    ]
+}
+
+// The line without its line ending, or nil once the input is exhausted.
+fn read_line(input: &mut dyn BufRead) -> Result<Value, String> {
+    let mut line = String::new();
+    if input
+        .read_line(&mut line)
+        .map_err(|error| error.to_string())?
+        == 0
+    {
+        return Ok(Value::Nil);
+    }
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+    Ok(Value::String(line))
+}
+
+// Everything left in the input, an empty string at EOF.
+fn read_all(input: &mut dyn BufRead) -> Result<Value, String> {
+    let mut all = String::new();
+    input
+        .read_to_string(&mut all)
+        .map_err(|error| error.to_string())?;
+    Ok(Value::String(all))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lox::interpreter::interpreter::tests::{interpret_with, variable};
+    use crate::lox::interpreter::interpreter::Interpreter;
+
+    #[test]
+    fn interpreters_share_standard_input() {
+        assert!(Rc::ptr_eq(&stdin(), &stdin()));
+    }
+
+    #[test]
+    fn input_natives_read_from_the_given_source() {
+        let input = std::io::Cursor::new("first\r\nsecond\nrest\nof it");
+        let source = "
+            var first = readLine();
+            var second = readLine();
+            var rest = readAll();
+            var end = readLine();
+            var empty = readAll();
+        ";
+        let interpreter = interpret_with(
+            Interpreter::with_input(Rc::new(RefCell::new(Box::new(input)))),
+            source,
+        );
+        assert_eq!(
+            variable(&interpreter, "first"),
+            Value::String("first".into())
+        );
+        assert_eq!(
+            variable(&interpreter, "second"),
+            Value::String("second".into())
+        );
+        assert_eq!(
+            variable(&interpreter, "rest"),
+            Value::String("rest\nof it".into())
+        );
+        assert_eq!(variable(&interpreter, "end"), Value::Nil);
+        assert_eq!(variable(&interpreter, "empty"), Value::String("".into()));
+    }
+}
@@ -1,178 +1,1008 @@
+use super::assert;
+use super::class::{Class, Instance};
 use super::environment::Environment;
+use super::file::{self, Sandbox};
+use super::function::Function;
+use super::input::{self, Input};
+use super::list::List;
+use super::map::{self, Key, Map};
+use super::math::{self, Rng};
+use super::module::Module;
+use super::native::NativeFunction;
+use super::prelude;
+use super::string;
+use crate::lox::expr::List as List_expr;
+use crate::lox::expr::Map as Map_expr;
 use crate::lox::expr::Var as Var_expr;
-use crate::lox::expr::{Assignment, Binary, Expr, Literal, Logical, Unary};
-use crate::lox::stmt::{Block, IfStmt, Stmt, Var, While};
-use crate::lox::token;
+use crate::lox::expr::{
+    Assignment, Binary, Call, Expr, Get, Index, Literal, Logical, Set, SetIndex, Super, Ternary,
+    This, Unary, Update,
+};
+use crate::lox::stmt::Class as Class_stmt;
+use crate::lox::stmt::Function as Function_stmt;
+use crate::lox::stmt::{Defer, IfStmt, Import, Return, Stmt, Throw, Try, Var, While};
+use crate::lox::token::{self, Span, Token};
+use crate::lox::{parser::Parser, resolver::Resolver, scanner::Scanner};
 use derive_more::Display;
-use std::cell::RefCell;
-use std::ops::{Add, Div, Mul, Neg, Not, Sub};
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::ops::{Add, BitAnd, BitOr, BitXor, Div, Mul, Neg, Not, Rem, Shl, Shr, Sub};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
+// Lox calls nest Rust calls, so runaway recursion has to be stopped before
+// it overflows the native stack. The interpreter needs a thread with
+// `STACK_SIZE` bytes of stack to get that deep, even in a debug build.
+const MAX_CALL_DEPTH: usize = 1000;
+pub const STACK_SIZE: usize = 256 * 1024 * 1024;
+
 #[derive(PartialEq, PartialOrd, Debug, Display, Clone)]
 pub enum Value {
     String(String),
+    Int(i64),
     F64(f64),
     Boolean(bool),
+    Function(Rc<Function>),
+    NativeFunction(Rc<NativeFunction>),
+    Class(Rc<Class>),
+    #[display(fmt = "{}", "_0.borrow()")]
+    Instance(Rc<RefCell<Instance>>),
+    #[display(fmt = "{}", "_0.borrow()")]
+    List(Rc<RefCell<List>>),
+    #[display(fmt = "{}", "_0.borrow()")]
+    Map(Rc<RefCell<Map>>),
+    Module(Rc<Module>),
     Nil,
 }
 
+#[derive(PartialEq, Debug, Clone)]
+pub struct RuntimeError {
+    pub token: Box<Token>,
+    pub message: String,
+    pub span: Span,
+    // The value of a `throw`, errors raised by the interpreter itself have none.
+    pub thrown: Option<Value>,
+    // The imported file the span points into, `None` for the script being run.
+    pub file: Option<Rc<Path>>,
+    located: bool,
+}
+
+impl RuntimeError {
+    pub fn new<S: Into<String>>(token: &Token, message: S) -> Self {
+        Self {
+            token: Box::new(token.clone()),
+            message: message.into(),
+            span: token.span,
+            thrown: None,
+            file: None,
+            located: false,
+        }
+    }
+
+    pub fn thrown(token: &Token, value: Value) -> Self {
+        let mut error = Self::new(token, format!("Uncaught exception: {}", value));
+        error.thrown = Some(value);
+        error
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = span;
+        self
+    }
+
+    // Errors are located by the first function or module they leave, which
+    // is the code the token came from.
+    fn located_in(mut self, file: &Option<Rc<Path>>) -> Self {
+        if !self.located {
+            self.file = file.clone();
+            self.located = true;
+        }
+        self
+    }
+
+    pub fn line(&self) -> u32 {
+        self.token.line
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}\n[line {}]", self.message, self.line())?;
+        match &self.file {
+            Some(file) => write!(f, " in {}", file.display()),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+enum LoopControl {
+    Break,
+    Continue,
+}
+
 #[derive(Debug)]
 pub struct Interpreter {
     env: Rc<RefCell<Environment>>,
+    // The top level of the script or module being run. It encloses
+    // `builtins`, which every module shares.
+    globals: Rc<RefCell<Environment>>,
+    builtins: Rc<RefCell<Environment>>,
+    return_value: Option<Value>,
+    loop_control: Option<LoopControl>,
+    // One list of deferred statements for every block being executed.
+    deferred: Vec<Vec<Stmt>>,
+    // Runtime errors are caught as instances of this class.
+    error_class: Rc<Class>,
+    // Shared with the random natives.
+    rng: Rc<RefCell<Rng>>,
+    sandbox: Sandbox,
+    // Imports are resolved relative to the directory of the running file.
+    module_dir: Option<PathBuf>,
+    // The imported file being run, `None` for the script itself.
+    file: Option<Rc<Path>>,
+    modules: HashMap<PathBuf, Rc<Module>>,
+    // The files being run, outermost first, for finding import cycles.
+    importing: Vec<PathBuf>,
+    // Shared with the assert natives.
+    assertions_passed: Rc<Cell<usize>>,
+    // Lox functions currently being called.
+    call_depth: usize,
 }
 
 impl Interpreter {
     pub fn new() -> Self {
-        let env = Rc::new(RefCell::new(Environment::new()));
-        Self { env }
+        Self::with_input(input::stdin())
+    }
+
+    // Like `new`, with `readLine` and `readAll` reading from `input`.
+    pub fn with_input(input: Input) -> Self {
+        let mut interpreter = Self::empty();
+        let natives = prelude::natives()
+            .into_iter()
+            .chain(map::natives())
+            .chain(string::natives())
+            .chain(math::natives(&interpreter.rng))
+            .chain(file::natives(&interpreter.sandbox))
+            .chain(input::natives(&input))
+            .chain(assert::natives(&interpreter.assertions_passed));
+        for native in natives {
+            interpreter.register_native(native);
+        }
+        for (name, value) in math::constants() {
+            interpreter.define_global(name, value);
+        }
+        interpreter
+    }
+
+    // An interpreter without any of the prelude natives.
+    pub fn empty() -> Self {
+        let builtins = Rc::new(RefCell::new(Environment::new()));
+        let mut globals = Environment::new();
+        globals.enclose(Rc::clone(&builtins));
+        let globals = Rc::new(RefCell::new(globals));
+        Self {
+            env: Rc::clone(&globals),
+            globals,
+            builtins,
+            return_value: None,
+            loop_control: None,
+            deferred: vec![],
+            error_class: Rc::new(Class::new("RuntimeError".into(), None, HashMap::new())),
+            rng: Rc::new(RefCell::new(Rng::from_time())),
+            sandbox: Rc::new(RefCell::new(None)),
+            module_dir: None,
+            file: None,
+            modules: HashMap::new(),
+            importing: vec![],
+            assertions_passed: Rc::new(Cell::new(0)),
+            call_depth: 0,
+        }
+    }
+
+    pub fn register_native(&mut self, native: NativeFunction) {
+        let name = native.name.clone();
+        self.define_global(&name, Value::NativeFunction(Rc::new(native)));
     }
 
-    pub fn evaluate_node(&mut self, stmt: Stmt) -> Value {
+    pub fn define_global(&mut self, name: &str, value: Value) {
+        self.builtins.borrow_mut().define(name, value);
+    }
+
+    // The file the statements come from, imports are relative to it.
+    pub fn set_script(&mut self, path: &Path) {
+        self.module_dir = path.parent().map(Path::to_path_buf);
+        self.importing = fs::canonicalize(path).into_iter().collect();
+    }
+
+    // Only lets the file natives use paths inside `root`.
+    pub fn sandbox(&mut self, root: PathBuf) {
+        *self.sandbox.borrow_mut() = Some(root);
+    }
+
+    pub fn assertions_passed(&self) -> usize {
+        self.assertions_passed.get()
+    }
+
+    // Makes `random` and `randomInt` deterministic.
+    pub fn seed(&mut self, seed: u64) {
+        *self.rng.borrow_mut() = Rng::new(seed);
+    }
+
+    pub fn evaluate_node(&mut self, stmt: &Stmt) -> Result<Value, RuntimeError> {
         match stmt {
             Stmt::Expr(expr) => self.evaluate_expression(expr),
             Stmt::Print(expr) => self.evaluate_print(expr),
             Stmt::Declaration(var) => self.evaluate_declaration(var),
-            Stmt::Block(block) => self.evaluate_block(block),
+            Stmt::Block(block) => self.evaluate_block(&block.stmt_vec),
             Stmt::If(block) => self.evaluate_if(block),
             Stmt::While(block) => self.evaluate_while(block),
+            Stmt::Function(function) => self.evaluate_function(function),
+            Stmt::Return(return_stmt) => self.evaluate_return(return_stmt),
+            Stmt::Class(class) => self.evaluate_class(class),
+            Stmt::Break(_) => {
+                self.loop_control = Some(LoopControl::Break);
+                Ok(Value::Nil)
+            }
+            Stmt::Continue(_) => {
+                self.loop_control = Some(LoopControl::Continue);
+                Ok(Value::Nil)
+            }
+            Stmt::Defer(defer) => self.evaluate_defer(defer),
+            Stmt::Throw(throw) => self.evaluate_throw(throw),
+            Stmt::Import(import) => self.evaluate_import(import),
+            Stmt::Try(try_stmt) => self.evaluate_try(try_stmt),
         }
     }
 
-    fn evaluate_expression(&mut self, expr: Expr) -> Value {
+    fn evaluate_expression(&mut self, expr: &Expr) -> Result<Value, RuntimeError> {
         match expr {
-            Expr::Literal(expr) => self.evaluate_literal(expr),
+            Expr::Literal(expr) => Ok(self.evaluate_literal(expr)),
             Expr::Logical(expr) => self.evaluate_logical(expr),
             Expr::Unary(expr) => self.evaluate_unary(expr),
             Expr::Binary(expr) => self.evaluate_binary(expr),
             Expr::Var(expr) => self.evaluate_variable(expr),
             Expr::Assignment(expr) => self.evaluate_assignment(expr),
-            Expr::Grouping(_) => panic!("Grouping not implemented"),
+            Expr::Call(expr) => self.evaluate_call(expr),
+            Expr::Get(expr) => self.evaluate_get(expr),
+            Expr::Set(expr) => self.evaluate_set(expr),
+            Expr::This(expr) => self.evaluate_this(expr),
+            Expr::Super(expr) => self.evaluate_super(expr),
+            Expr::List(expr) => self.evaluate_list(expr),
+            Expr::Map(expr) => self.evaluate_map(expr),
+            Expr::Index(expr) => self.evaluate_index(expr),
+            Expr::SetIndex(expr) => self.evaluate_set_index(expr),
+            Expr::Grouping(expr) => self.evaluate_expression(&expr.expr),
+            Expr::Update(expr) => self.evaluate_update(expr),
+            Expr::Ternary(expr) => self.evaluate_ternary(expr),
+            Expr::Stringify(expr) => {
+                let value = self.evaluate_expression(&expr.expr)?;
+                Ok(Value::String(value.to_string()))
+            }
         }
     }
 
-    fn evaluate_print(&mut self, expr: Expr) -> Value {
-        let value = self.evaluate_expression(expr);
+    fn evaluate_print(&mut self, expr: &Expr) -> Result<Value, RuntimeError> {
+        let value = self.evaluate_expression(expr)?;
         println!("{}", value);
-        Value::Nil
+        Ok(Value::Nil)
+    }
+
+    fn evaluate_ternary(&mut self, expr: &Ternary) -> Result<Value, RuntimeError> {
+        if self.evaluate_expression(&expr.condition)?.truthyness() {
+            self.evaluate_expression(&expr.then_branch)
+        } else {
+            self.evaluate_expression(&expr.else_branch)
+        }
     }
 
-    fn evaluate_logical(&mut self, expr: Logical) -> Value {
-        let left = self.evaluate_expression(*expr.left);
+    fn evaluate_logical(&mut self, expr: &Logical) -> Result<Value, RuntimeError> {
+        let left = self.evaluate_expression(&expr.left)?;
 
         match expr.operator.t_type {
             token::TokenType::Or => {
                 if left.truthyness() {
-                    return left;
+                    return Ok(left);
                 }
-                self.evaluate_expression(*expr.right)
+                self.evaluate_expression(&expr.right)
             }
             token::TokenType::And => {
                 if !left.truthyness() {
-                    return left;
+                    return Ok(left);
                 }
 
-                self.evaluate_expression(*expr.right)
+                self.evaluate_expression(&expr.right)
             }
             _ => panic!("Not a logical operator"),
         }
     }
 
-    fn evaluate_declaration(&mut self, var: Var) -> Value {
-        let value = self.evaluate_expression(var.value);
-        self.env.borrow_mut().define(&var.name, value);
-        self.env.borrow_mut().get(&var.name).unwrap()
+    fn evaluate_declaration(&mut self, var: &Var) -> Result<Value, RuntimeError> {
+        let value = self.evaluate_expression(&var.value)?;
+        self.env
+            .borrow_mut()
+            .define(&var.name.lexeme, value.clone());
+        Ok(value)
     }
 
-    fn evaluate_block(&mut self, block: Block) -> Value {
-        let prev_env = Rc::clone(&self.env);
-        let env = Rc::new(RefCell::new(Environment::new()));
-        self.env = env;
-        self.env.borrow_mut().enclose(prev_env.clone());
-        for stmt in block.stmt_vec {
-            self.evaluate_node(stmt);
+    fn evaluate_block(&mut self, stmts: &[Stmt]) -> Result<Value, RuntimeError> {
+        let mut env = Environment::new();
+        env.enclose(Rc::clone(&self.env));
+        self.execute_block(stmts, Rc::new(RefCell::new(env)))
+    }
+
+    fn execute_block(
+        &mut self,
+        stmts: &[Stmt],
+        env: Rc<RefCell<Environment>>,
+    ) -> Result<Value, RuntimeError> {
+        let prev_env = std::mem::replace(&mut self.env, env);
+        self.deferred.push(vec![]);
+        let mut result = self.execute_stmts(stmts);
+        // Deferred statements still see the block's environment.
+        let deferred = self.deferred.pop().unwrap_or_default();
+        for stmt in deferred.iter().rev() {
+            result = self.execute_cleanup(|interpreter| interpreter.evaluate_node(stmt), result);
         }
         self.env = prev_env;
-        Value::Nil
+        result
+    }
+
+    // Runs a finally block or deferred statement after `result`. A pending
+    // return, break or continue is held back while it runs, unless the
+    // cleanup jumps somewhere itself, and an error from it replaces `result`.
+    fn execute_cleanup(
+        &mut self,
+        cleanup: impl FnOnce(&mut Self) -> Result<Value, RuntimeError>,
+        result: Result<Value, RuntimeError>,
+    ) -> Result<Value, RuntimeError> {
+        let return_value = self.return_value.take();
+        let loop_control = self.loop_control.take();
+        cleanup(self)?;
+        if self.return_value.is_some() || self.loop_control.is_some() {
+            return Ok(Value::Nil);
+        }
+        self.return_value = return_value;
+        self.loop_control = loop_control;
+        result
+    }
+
+    fn execute_stmts(&mut self, stmts: &[Stmt]) -> Result<Value, RuntimeError> {
+        for stmt in stmts {
+            self.evaluate_node(stmt)?;
+            if self.return_value.is_some() || self.loop_control.is_some() {
+                break;
+            }
+        }
+        Ok(Value::Nil)
+    }
+
+    fn evaluate_throw(&mut self, throw: &Throw) -> Result<Value, RuntimeError> {
+        let span = throw.keyword.span.merge(throw.value.span());
+        let value = self.evaluate_expression(&throw.value)?;
+        Err(RuntimeError::thrown(&throw.keyword, value).with_span(span))
+    }
+
+    fn evaluate_try(&mut self, try_stmt: &Try) -> Result<Value, RuntimeError> {
+        let mut result = self.evaluate_block(&try_stmt.body);
+
+        if let Some(catch) = &try_stmt.catch {
+            if let Err(error) = result {
+                let mut env = Environment::new();
+                env.enclose(Rc::clone(&self.env));
+                env.define(&catch.name.lexeme, self.exception_value(error));
+                result = self.execute_block(&catch.body, Rc::new(RefCell::new(env)));
+            }
+        }
+
+        match &try_stmt.finally {
+            Some(finally) => {
+                self.execute_cleanup(|interpreter| interpreter.evaluate_block(finally), result)
+            }
+            None => result,
+        }
+    }
+
+    // The resolver rejects `defer` outside of a block, so there is always a
+    // block to queue it on.
+    fn evaluate_defer(&mut self, defer: &Defer) -> Result<Value, RuntimeError> {
+        match self.deferred.last_mut() {
+            Some(deferred) => deferred.push((*defer.body).clone()),
+            None => unreachable!("'defer' outside of a block got past the resolver"),
+        }
+        Ok(Value::Nil)
+    }
+
+    fn exception_value(&self, error: RuntimeError) -> Value {
+        if let Some(value) = error.thrown {
+            return value;
+        }
+        let line = i64::from(error.line());
+        let mut instance = Instance::new(Rc::clone(&self.error_class));
+        instance.set("message", Value::String(error.message));
+        instance.set("line", Value::Int(line));
+        Value::Instance(Rc::new(RefCell::new(instance)))
+    }
+
+    fn evaluate_import(&mut self, import: &Import) -> Result<Value, RuntimeError> {
+        let relative = match &import.path.literal {
+            token::Literal::String(path) => path.clone(),
+            _ => unreachable!(),
+        };
+        let error = |message: String| RuntimeError::new(&import.path, message);
+
+        let path = self
+            .module_dir
+            .as_deref()
+            .unwrap_or_else(|| Path::new("."))
+            .join(&relative);
+        // Modules are files too, so they have to be inside the sandbox.
+        let path = fs::canonicalize(&path)
+            .and_then(|path| file::resolve(self.sandbox.borrow().as_deref(), &path))
+            .map_err(|io_error| error(format!("Can't import '{}': {}", relative, io_error)))?;
+
+        let module = match self.modules.get(&path) {
+            Some(module) => Rc::clone(module),
+            None => {
+                if let Some(start) = self.importing.iter().position(|file| *file == path) {
+                    let cycle: Vec<String> = self.importing[start..]
+                        .iter()
+                        .chain(std::iter::once(&path))
+                        .map(|file| {
+                            file.file_name()
+                                .unwrap_or_default()
+                                .to_string_lossy()
+                                .into()
+                        })
+                        .collect();
+                    return Err(error(format!("Import cycle: {}.", cycle.join(" -> "))));
+                }
+                let module = self.load_module(&path, &relative, &import.path)?;
+                self.modules.insert(path, Rc::clone(&module));
+                module
+            }
+        };
+        self.env
+            .borrow_mut()
+            .define(&import.name.lexeme, Value::Module(module));
+        Ok(Value::Nil)
     }
-    fn evaluate_assignment(&mut self, assignment_expr: Assignment) -> Value {
-        let expr = assignment_expr.value;
-        let value = self.evaluate_expression(*expr);
-        let name = assignment_expr.name.lexeme;
-        self.env.borrow_mut().assign(&name, value);
-        let value = self.env.borrow_mut().get(&name);
-        value.unwrap()
+
+    // Runs the file once in its own top level environment, errors are
+    // reported at the import with the module's line added to the message.
+    fn load_module(
+        &mut self,
+        path: &Path,
+        relative: &str,
+        import_path: &Token,
+    ) -> Result<Rc<Module>, RuntimeError> {
+        let error = |message: String| RuntimeError::new(import_path, message);
+        let source = fs::read_to_string(path)
+            .map_err(|io_error| error(format!("Can't import '{}': {}", relative, io_error)))?;
+        let statements = Interpreter::parse_module(&source)
+            .map_err(|message| error(format!("Can't import '{}':\n{}", relative, message)))?;
+
+        let mut env = Environment::new();
+        env.enclose(Rc::clone(&self.builtins));
+        let env = Rc::new(RefCell::new(env));
+        let enclosing_globals = std::mem::replace(&mut self.globals, Rc::clone(&env));
+        let enclosing_dir =
+            std::mem::replace(&mut self.module_dir, path.parent().map(Path::to_path_buf));
+        let file: Option<Rc<Path>> = Some(path.into());
+        let enclosing_file = std::mem::replace(&mut self.file, file.clone());
+        self.importing.push(path.to_path_buf());
+        let result = self.execute_block(&statements, Rc::clone(&env));
+        self.importing.pop();
+        self.file = enclosing_file;
+        self.module_dir = enclosing_dir;
+        self.globals = enclosing_globals;
+
+        if let Err(module_error) = result {
+            let module_error = module_error.located_in(&file);
+            let location = match &module_error.file {
+                Some(error_file) if **error_file != *path => error_file.display().to_string(),
+                _ => relative.to_string(),
+            };
+            let message = format!(
+                "{}\n[line {}] in {}",
+                module_error.message,
+                module_error.line(),
+                location
+            );
+            let mut wrapped = error(message);
+            wrapped.thrown = module_error.thrown;
+            return Err(wrapped);
+        }
+
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        Ok(Rc::new(Module::new(name.into(), env)))
+    }
+
+    pub fn parse_module(source: &str) -> Result<Vec<Stmt>, String> {
+        let join = |errors: Vec<String>| errors.join("\n");
+        let (tokens, errors) = Scanner::new(source).scan_text();
+        if !errors.is_empty() {
+            return Err(join(errors.iter().map(ToString::to_string).collect()));
+        }
+        let (mut statements, errors) = Parser::new(&tokens).parse();
+        if !errors.is_empty() {
+            return Err(join(errors.iter().map(ToString::to_string).collect()));
+        }
+        if let Err(errors) = Resolver::new().resolve(&mut statements) {
+            return Err(join(errors.iter().map(ToString::to_string).collect()));
+        }
+        Ok(statements)
+    }
+
+    fn evaluate_function(
+        &mut self,
+        declaration: &Rc<Function_stmt>,
+    ) -> Result<Value, RuntimeError> {
+        let name = declaration.name.lexeme.clone();
+        let function = Function::new(
+            Rc::clone(declaration),
+            Rc::clone(&self.env),
+            Rc::clone(&self.globals),
+            self.file.clone(),
+        );
+        self.env
+            .borrow_mut()
+            .define(&name, Value::Function(Rc::new(function)));
+        Ok(Value::Nil)
+    }
+
+    fn evaluate_return(&mut self, return_stmt: &Return) -> Result<Value, RuntimeError> {
+        let value = match &return_stmt.value {
+            Some(expr) => self.evaluate_expression(expr)?,
+            None => Value::Nil,
+        };
+        self.return_value = Some(value);
+        Ok(Value::Nil)
     }
 
-    fn evaluate_if(&mut self, if_statement: IfStmt) -> Value {
-        let truth_branch = *if_statement.truth_branch;
-        let false_branch = *if_statement.false_branch;
-        let condition = self.evaluate_expression(if_statement.condition);
+    fn evaluate_call(&mut self, call: &Call) -> Result<Value, RuntimeError> {
+        let span = call.callee.span().merge(call.paren.span);
+        let callee = self.evaluate_expression(&call.callee)?;
+        let mut arguments = Vec::with_capacity(call.arguments.len());
+        for argument in &call.arguments {
+            arguments.push(self.evaluate_expression(argument)?);
+        }
+
+        let arity = match &callee {
+            Value::Function(function) => function.arity(),
+            Value::NativeFunction(native) => native.arity,
+            Value::Class(class) => class.arity(),
+            _ => {
+                return Err(
+                    RuntimeError::new(&call.paren, "Can only call functions and classes.")
+                        .with_span(span),
+                )
+            }
+        };
+        if arguments.len() != arity {
+            return Err(RuntimeError::new(
+                &call.paren,
+                format!("Expected {} arguments but got {}.", arity, arguments.len()),
+            )
+            .with_span(span));
+        }
+
+        let calls_lox = matches!(callee, Value::Function(_) | Value::Class(_));
+        if calls_lox && self.call_depth >= MAX_CALL_DEPTH {
+            return Err(RuntimeError::new(&call.paren, "Stack overflow.").with_span(span));
+        }
+
+        match callee {
+            Value::Function(function) => self.call_function(&function, arguments),
+            Value::Class(class) => self.instantiate(class, arguments),
+            Value::NativeFunction(native) => {
+                let paren = &call.paren;
+                native
+                    .call(&arguments)
+                    .map_err(|message| RuntimeError::new(paren, message).with_span(span))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn instantiate(
+        &mut self,
+        class: Rc<Class>,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        let instance = Value::Instance(Rc::new(RefCell::new(Instance::new(Rc::clone(&class)))));
+        if let Some(initializer) = class.find_method("init") {
+            self.call_function(&initializer.bind(instance.clone()), arguments)?;
+        }
+        Ok(instance)
+    }
+
+    fn call_function(
+        &mut self,
+        function: &Function,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        let mut env = Environment::new();
+        env.enclose(Rc::clone(&function.closure));
+        for (param, argument) in function.declaration.params.iter().zip(arguments) {
+            env.define(&param.lexeme, argument);
+        }
+
+        let enclosing_globals = std::mem::replace(&mut self.globals, Rc::clone(&function.globals));
+        let enclosing_file = std::mem::replace(&mut self.file, function.file.clone());
+        self.call_depth += 1;
+        let result = self.execute_block(&function.declaration.body, Rc::new(RefCell::new(env)));
+        self.call_depth -= 1;
+        self.file = enclosing_file;
+        self.globals = enclosing_globals;
+        let value = self.return_value.take().unwrap_or(Value::Nil);
+        result.map_err(|error| error.located_in(&function.file))?;
+
+        if function.is_initializer {
+            return Ok(function
+                .closure
+                .borrow()
+                .get_at(0, "this")
+                .unwrap_or(Value::Nil));
+        }
+        Ok(value)
+    }
+
+    fn evaluate_class(&mut self, class_stmt: &Class_stmt) -> Result<Value, RuntimeError> {
+        let superclass = match &class_stmt.superclass {
+            Some(superclass) => match self.evaluate_variable(superclass)? {
+                Value::Class(class) => Some(class),
+                _ => {
+                    return Err(RuntimeError::new(
+                        &superclass.name,
+                        "Superclass must be a class.",
+                    ))
+                }
+            },
+            None => None,
+        };
+
+        let method_env = match &superclass {
+            Some(superclass) => {
+                let mut env = Environment::new();
+                env.enclose(Rc::clone(&self.env));
+                env.define("super", Value::Class(Rc::clone(superclass)));
+                Rc::new(RefCell::new(env))
+            }
+            None => Rc::clone(&self.env),
+        };
+
+        let mut methods = HashMap::new();
+        for method in &class_stmt.methods {
+            let name = method.name.lexeme.clone();
+            let function = Function::method(
+                Rc::clone(method),
+                Rc::clone(&method_env),
+                Rc::clone(&self.globals),
+                self.file.clone(),
+            );
+            methods.insert(name, Rc::new(function));
+        }
+
+        let name = &class_stmt.name.lexeme;
+        let class = Class::new(name.clone(), superclass, methods);
+        self.env
+            .borrow_mut()
+            .define(name, Value::Class(Rc::new(class)));
+        Ok(Value::Nil)
+    }
+
+    fn evaluate_get(&mut self, get: &Get) -> Result<Value, RuntimeError> {
+        match self.evaluate_expression(&get.object)? {
+            Value::Instance(instance) => match Instance::get(&instance, &get.name.lexeme) {
+                Some(value) => Ok(value),
+                None => Err(RuntimeError::new(
+                    &get.name,
+                    format!("Undefined property '{}'.", get.name.lexeme),
+                )),
+            },
+            Value::Module(module) => match module.get(&get.name.lexeme) {
+                Some(value) => Ok(value),
+                None => Err(RuntimeError::new(
+                    &get.name,
+                    format!(
+                        "Module '{}' has no member '{}'.",
+                        module.name, get.name.lexeme
+                    ),
+                )),
+            },
+            _ => Err(RuntimeError::new(
+                &get.name,
+                "Only instances have properties.",
+            )),
+        }
+    }
+
+    fn evaluate_set(&mut self, set: &Set) -> Result<Value, RuntimeError> {
+        let instance = match self.evaluate_expression(&set.object)? {
+            Value::Instance(instance) => instance,
+            _ => return Err(RuntimeError::new(&set.name, "Only instances have fields.")),
+        };
+        let value = self.evaluate_expression(&set.value)?;
+        instance.borrow_mut().set(&set.name.lexeme, value.clone());
+        Ok(value)
+    }
+
+    fn evaluate_list(&mut self, list: &List_expr) -> Result<Value, RuntimeError> {
+        let mut elements = Vec::with_capacity(list.elements.len());
+        for element in &list.elements {
+            elements.push(self.evaluate_expression(element)?);
+        }
+        Ok(Value::List(Rc::new(RefCell::new(List::new(elements)))))
+    }
+
+    fn evaluate_map(&mut self, map: &Map_expr) -> Result<Value, RuntimeError> {
+        let left_brace = &map.left_brace;
+        let mut entries = BTreeMap::new();
+        for (key_expr, value) in &map.entries {
+            let span = key_expr.span();
+            let key = self.evaluate_expression(key_expr)?;
+            let key = Key::from_value(&key)
+                .map_err(|message| RuntimeError::new(left_brace, message).with_span(span))?;
+            entries.insert(key, self.evaluate_expression(value)?);
+        }
+        Ok(Value::Map(Rc::new(RefCell::new(Map::new(entries)))))
+    }
+
+    fn evaluate_index(&mut self, index: &Index) -> Result<Value, RuntimeError> {
+        let span = index.object.span().merge(index.bracket.span);
+        let object = self.evaluate_expression(&index.object)?;
+        let position = self.evaluate_expression(&index.index)?;
+        let bracket = &index.bracket;
+        object
+            .get_index(&position)
+            .map_err(|message| RuntimeError::new(bracket, message).with_span(span))
+    }
+
+    fn evaluate_set_index(&mut self, set: &SetIndex) -> Result<Value, RuntimeError> {
+        let span = set.object.span().merge(set.bracket.span);
+        let object = self.evaluate_expression(&set.object)?;
+        let position = self.evaluate_expression(&set.index)?;
+        let value = self.evaluate_expression(&set.value)?;
+        let bracket = &set.bracket;
+        object
+            .set_index(&position, value.clone())
+            .map_err(|message| RuntimeError::new(bracket, message).with_span(span))?;
+        Ok(value)
+    }
+
+    fn evaluate_this(&mut self, this: &This) -> Result<Value, RuntimeError> {
+        match self.look_up_variable(&this.keyword.lexeme, this.depth) {
+            Some(value) => Ok(value),
+            None => Err(RuntimeError::new(
+                &this.keyword,
+                "Can't use 'this' outside of a class.",
+            )),
+        }
+    }
+
+    fn evaluate_super(&mut self, super_expr: &Super) -> Result<Value, RuntimeError> {
+        // "this" always lives in the scope right inside the one holding "super".
+        let depth = super_expr.depth.unwrap_or(0);
+        let superclass = self.env.borrow().get_at(depth, "super");
+        let instance = self.env.borrow().get_at(depth.saturating_sub(1), "this");
+        match (superclass, instance) {
+            (Some(Value::Class(superclass)), Some(instance)) => {
+                match superclass.find_method(&super_expr.method.lexeme) {
+                    Some(method) => Ok(Value::Function(Rc::new(method.bind(instance)))),
+                    None => Err(RuntimeError::new(
+                        &super_expr.method,
+                        format!("Undefined property '{}'.", super_expr.method.lexeme),
+                    )),
+                }
+            }
+            _ => Err(RuntimeError::new(
+                &super_expr.keyword,
+                "Can't use 'super' outside of a subclass.",
+            )),
+        }
+    }
+
+    fn evaluate_assignment(&mut self, assignment_expr: &Assignment) -> Result<Value, RuntimeError> {
+        let value = self.evaluate_expression(&assignment_expr.value)?;
+        self.assign_variable(&assignment_expr.name, assignment_expr.depth, value.clone())?;
+        Ok(value)
+    }
+
+    fn assign_variable(
+        &mut self,
+        name: &Token,
+        depth: Option<usize>,
+        value: Value,
+    ) -> Result<(), RuntimeError> {
+        let assigned = match depth {
+            Some(depth) => self.env.borrow_mut().assign_at(depth, &name.lexeme, value),
+            None => self.globals.borrow_mut().assign(&name.lexeme, value),
+        };
+        assigned.map_err(|message| RuntimeError::new(name, message))
+    }
+
+    // Reads the target, combines it with the value and writes it back, with
+    // every part of the target expression evaluated exactly once.
+    fn evaluate_update(&mut self, update: &Update) -> Result<Value, RuntimeError> {
+        let span = update.target.span().merge(update.value.span());
+        let operator = &update.operator;
+        let value = &update.value;
+        let error = |message: String| RuntimeError::new(operator, message).with_span(span);
+
+        let (old, new) = match &*update.target {
+            Expr::Var(var) => {
+                let old = self.evaluate_variable(var)?;
+                let new = self.apply_update(operator, old.clone(), value, span)?;
+                self.assign_variable(&var.name, var.depth, new.clone())?;
+                (old, new)
+            }
+            Expr::Get(get) => {
+                let instance = match self.evaluate_expression(&get.object)? {
+                    Value::Instance(instance) => instance,
+                    _ => return Err(RuntimeError::new(&get.name, "Only instances have fields.")),
+                };
+                let old = match Instance::get(&instance, &get.name.lexeme) {
+                    Some(old) => old,
+                    None => {
+                        return Err(RuntimeError::new(
+                            &get.name,
+                            format!("Undefined property '{}'.", get.name.lexeme),
+                        ))
+                    }
+                };
+                let new = self.apply_update(operator, old.clone(), value, span)?;
+                instance.borrow_mut().set(&get.name.lexeme, new.clone());
+                (old, new)
+            }
+            Expr::Index(index) => {
+                let object = self.evaluate_expression(&index.object)?;
+                let position = self.evaluate_expression(&index.index)?;
+                let old = object.get_index(&position).map_err(error)?;
+                let new = self.apply_update(operator, old.clone(), value, span)?;
+                object.set_index(&position, new.clone()).map_err(error)?;
+                (old, new)
+            }
+            _ => return Err(error("Invalid assignment target.".into())),
+        };
+
+        Ok(if update.postfix { old } else { new })
+    }
+
+    fn apply_update(
+        &mut self,
+        operator: &Token,
+        old: Value,
+        value: &Expr,
+        span: Span,
+    ) -> Result<Value, RuntimeError> {
+        let value = self.evaluate_expression(value)?;
+        let result = match operator.t_type {
+            token::TokenType::PlusEqual | token::TokenType::PlusPlus => old + value,
+            token::TokenType::MinusEqual | token::TokenType::MinusMinus => old - value,
+            token::TokenType::StarEqual => old * value,
+            token::TokenType::SlashEqual => old / value,
+            _ => Err("Unknown update operator."),
+        };
+        result.map_err(|msg| RuntimeError::new(operator, msg).with_span(span))
+    }
+
+    fn evaluate_if(&mut self, if_statement: &IfStmt) -> Result<Value, RuntimeError> {
+        let condition = self.evaluate_expression(&if_statement.condition)?;
 
         if condition.truthyness() {
-            self.evaluate_node(truth_branch)
-        } else if false_branch != None {
-            self.evaluate_node(false_branch.unwrap())
+            self.evaluate_node(&if_statement.truth_branch)
+        } else if let Some(false_branch) = &*if_statement.false_branch {
+            self.evaluate_node(false_branch)
         } else {
-            Value::Nil
+            Ok(Value::Nil)
         }
     }
 
-    fn evaluate_while(&mut self, while_stmt: While) -> Value {
-        let mut condition = self.evaluate_expression(while_stmt.condition.clone());
+    fn evaluate_while(&mut self, while_stmt: &While) -> Result<Value, RuntimeError> {
+        let mut condition = self.evaluate_expression(&while_stmt.condition)?;
         while condition.truthyness() {
-            let body = *while_stmt.body.clone();
-            self.evaluate_node(body);
-            condition = self.evaluate_expression(while_stmt.condition.clone());
+            self.evaluate_node(&while_stmt.body)?;
+            if self.return_value.is_some() || self.loop_control.take() == Some(LoopControl::Break) {
+                break;
+            }
+            if let Some(increment) = &while_stmt.increment {
+                self.evaluate_expression(increment)?;
+            }
+            condition = self.evaluate_expression(&while_stmt.condition)?;
         }
 
-        Value::Nil
+        Ok(Value::Nil)
+    }
+
+    fn evaluate_variable(&mut self, expr: &Var_expr) -> Result<Value, RuntimeError> {
+        match self.look_up_variable(&expr.name.lexeme, expr.depth) {
+            Some(value) => Ok(value),
+            None => Err(RuntimeError::new(
+                &expr.name,
+                format!("Undefined variable '{}'.", expr.name.lexeme),
+            )),
+        }
     }
 
-    fn evaluate_variable(&mut self, expr: Var_expr) -> Value {
-        let identifier = expr.name;
-        let name = identifier.lexeme;
-        match self.env.borrow_mut().get(&name) {
-            Some(value) => value,
-            None => panic!("The variable {var_name} doesn't exist", var_name = name),
+    fn look_up_variable(&self, name: &str, depth: Option<usize>) -> Option<Value> {
+        match depth {
+            Some(depth) => self.env.borrow().get_at(depth, name),
+            None => self.globals.borrow().get(name),
         }
     }
 
-    fn evaluate_literal(&mut self, expr: Literal) -> Value {
-        match expr.token.literal {
-            token::Literal::String(string) => Value::String(string),
-            token::Literal::F64(f64) => Value::F64(f64),
-            token::Literal::Boolean(boolean) => Value::Boolean(boolean),
+    fn evaluate_literal(&mut self, expr: &Literal) -> Value {
+        match &expr.token.literal {
+            token::Literal::String(string) => Value::String(string.clone()),
+            token::Literal::Int(int) => Value::Int(*int),
+            token::Literal::F64(f64) => Value::F64(*f64),
+            token::Literal::Boolean(boolean) => Value::Boolean(*boolean),
             _ => Value::Nil,
         }
     }
 
-    fn evaluate_unary(&mut self, unary_expr: Unary) -> Value {
-        let value = self.evaluate_expression(*unary_expr.expr);
+    fn evaluate_unary(&mut self, unary_expr: &Unary) -> Result<Value, RuntimeError> {
+        let span = unary_expr.operator.span.merge(unary_expr.expr.span());
+        let value = self.evaluate_expression(&unary_expr.expr)?;
+        let operator = &unary_expr.operator;
 
-        match unary_expr.operator.t_type {
-            token::TokenType::Minus => -value,
-            token::TokenType::Bang => !value,
-            _ => Value::Nil,
+        match operator.t_type {
+            token::TokenType::Minus => {
+                (-value).map_err(|msg| RuntimeError::new(operator, msg).with_span(span))
+            }
+            token::TokenType::Bang => Ok(!value),
+            token::TokenType::Tilde => value
+                .bit_not()
+                .map_err(|msg| RuntimeError::new(operator, msg).with_span(span)),
+            _ => Ok(Value::Nil),
         }
     }
 
-    fn evaluate_binary(&mut self, expr: Binary) -> Value {
-        let left_value = self.evaluate_expression(*expr.left);
-        let right_value = self.evaluate_expression(*expr.right);
+    fn evaluate_binary(&mut self, expr: &Binary) -> Result<Value, RuntimeError> {
+        let span = expr.left.span().merge(expr.right.span());
+        let left_value = self.evaluate_expression(&expr.left)?;
+        let right_value = self.evaluate_expression(&expr.right)?;
+        let operator = &expr.operator;
 
-        match expr.operator.t_type {
+        let result = match operator.t_type {
             token::TokenType::Plus => left_value + right_value,
             token::TokenType::Minus => left_value - right_value,
             token::TokenType::Slash => left_value / right_value,
             token::TokenType::Star => left_value * right_value,
-            token::TokenType::Greater => Value::Boolean(left_value > right_value),
-            token::TokenType::GreaterEqual => Value::Boolean(left_value >= right_value),
-            token::TokenType::Less => Value::Boolean(left_value < right_value),
-            token::TokenType::LessEqual => Value::Boolean(left_value <= right_value),
-            token::TokenType::EqualEqual => Value::Boolean(left_value == right_value),
-            token::TokenType::BangEqual => Value::Boolean(left_value != right_value),
-            _ => panic!("Not implemented"),
-        }
+            token::TokenType::Percent => left_value % right_value,
+            token::TokenType::StarStar => left_value.pow(right_value),
+            token::TokenType::Ampersand => left_value & right_value,
+            token::TokenType::Pipe => left_value | right_value,
+            token::TokenType::Caret => left_value ^ right_value,
+            token::TokenType::LessLess => left_value << right_value,
+            token::TokenType::GreaterGreater => left_value >> right_value,
+            token::TokenType::EqualEqual => Ok(Value::Boolean(left_value.equals(&right_value))),
+            token::TokenType::BangEqual => Ok(Value::Boolean(!left_value.equals(&right_value))),
+            token::TokenType::Greater
+            | token::TokenType::GreaterEqual
+            | token::TokenType::Less
+            | token::TokenType::LessEqual => {
+                let ordering = left_value.compare(&right_value);
+                ordering.map(|ordering| {
+                    Value::Boolean(match operator.t_type {
+                        token::TokenType::Greater => ordering == Some(Ordering::Greater),
+                        token::TokenType::GreaterEqual => {
+                            matches!(ordering, Some(Ordering::Greater | Ordering::Equal))
+                        }
+                        token::TokenType::Less => ordering == Some(Ordering::Less),
+                        _ => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                    })
+                })
+            }
+            _ => Err("Unknown binary operator."),
+        };
+
+        result.map_err(|msg| RuntimeError::new(operator, msg).with_span(span))
     }
 }
 impl Value {
@@ -183,64 +1013,299 @@ impl Value {
             _ => true,
         }
     }
+
+    fn get_index(&self, index: &Value) -> Result<Value, String> {
+        match self {
+            Value::List(list) => {
+                let list = list.borrow();
+                let position = list.position(index)?;
+                Ok(list.elements[position].clone())
+            }
+            Value::Map(map) => {
+                let key = Key::from_value(index)?;
+                match map.borrow().entries.get(&key) {
+                    Some(value) => Ok(value.clone()),
+                    None => Err(format!("Undefined key '{}'.", key)),
+                }
+            }
+            _ => Err("Only lists and maps can be indexed.".into()),
+        }
+    }
+
+    fn set_index(&self, index: &Value, value: Value) -> Result<(), String> {
+        match self {
+            Value::List(list) => {
+                let mut list = list.borrow_mut();
+                let position = list.position(index)?;
+                list.elements[position] = value;
+                Ok(())
+            }
+            Value::Map(map) => {
+                let key = Key::from_value(index)?;
+                map.borrow_mut().entries.insert(key, value);
+                Ok(())
+            }
+            _ => Err("Only lists and maps can be indexed.".into()),
+        }
+    }
+
+    // Integers only turn into floats when mixed with one, or for a negative
+    // exponent.
+    pub fn pow(self, rhs: Self) -> Result<Self, &'static str> {
+        match (self, rhs) {
+            (Value::Int(left), Value::Int(right)) if right >= 0 => {
+                let result = u32::try_from(right)
+                    .ok()
+                    .and_then(|right| left.checked_pow(right));
+                result.map(Value::Int).ok_or("Integer overflow.")
+            }
+            (left, right) => match (left.as_f64(), right.as_f64()) {
+                (Some(left), Some(right)) => Ok(Value::F64(left.powf(right))),
+                (_, _) => Err("Operands must be numbers."),
+            },
+        }
+    }
+
+    fn bit_not(self) -> Result<Self, &'static str> {
+        match self.integral() {
+            Some(value) => Ok(Value::Int(!value)),
+            None => Err("Operand must be an integer."),
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::Int(value) => Some(value as f64),
+            Value::F64(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    // Bitwise operators work on integers, and on floats with no fractional
+    // part that fit in an i64.
+    pub fn integral(&self) -> Option<i64> {
+        match *self {
+            Value::Int(value) => Some(value),
+            Value::F64(value)
+                if value.fract() == 0.0 && value >= i64::MIN as f64 && value < i64::MAX as f64 =>
+            {
+                Some(value as i64)
+            }
+            _ => None,
+        }
+    }
+
+    fn integral_operands(self, rhs: Self) -> Result<(i64, i64), &'static str> {
+        match (self.integral(), rhs.integral()) {
+            (Some(left), Some(right)) => Ok((left, right)),
+            (_, _) => Err("Operands must be integers."),
+        }
+    }
+
+    fn shift_amount(amount: i64) -> Result<u32, &'static str> {
+        match amount {
+            0..=63 => Ok(amount as u32),
+            _ => Err("Shift amount must be between 0 and 63."),
+        }
+    }
+
+    // Two integers use the checked integer operation, anything mixing in a
+    // float is done in floating point.
+    fn arithmetic(
+        self,
+        rhs: Self,
+        int_op: fn(i64, i64) -> Result<i64, &'static str>,
+        float_op: fn(f64, f64) -> f64,
+    ) -> Result<Self, &'static str> {
+        match (self, rhs) {
+            (Value::Int(left), Value::Int(right)) => int_op(left, right).map(Value::Int),
+            (left, right) => match (left.as_f64(), right.as_f64()) {
+                (Some(left), Some(right)) => Ok(Value::F64(float_op(left, right))),
+                (_, _) => Err("Operands must be numbers."),
+            },
+        }
+    }
+
+    // Numbers are equal by value whatever their type, so `1 == 1.0`.
+    pub fn equals(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Int(_), Value::F64(_)) | (Value::F64(_), Value::Int(_)) => {
+                self.compare(other) == Ok(Some(Ordering::Equal))
+            }
+            (_, _) => self == other,
+        }
+    }
+
+    // NaN is unordered against everything, the same way f64 does, so every
+    // comparison with it is false.
+    fn compare(&self, other: &Self) -> Result<Option<Ordering>, &'static str> {
+        match (self, other) {
+            (Value::Int(left), Value::Int(right)) => Ok(Some(left.cmp(right))),
+            (Value::F64(left), Value::F64(right)) => Ok(left.partial_cmp(right)),
+            (Value::Int(left), Value::F64(right)) => Ok(compare_int_float(*left, *right)),
+            (Value::F64(left), Value::Int(right)) => {
+                Ok(compare_int_float(*right, *left).map(Ordering::reverse))
+            }
+            (Value::String(left), Value::String(right)) => Ok(Some(left.cmp(right))),
+            (_, _) => Err("Operands must be two numbers or two strings."),
+        }
+    }
+}
+
+// Exact, unlike converting the integer to a float which rounds above 2^53.
+fn compare_int_float(int: i64, float: f64) -> Option<Ordering> {
+    const LIMIT: f64 = 9_223_372_036_854_775_808.0; // 2^63
+    if float.is_nan() {
+        return None;
+    }
+    if float >= LIMIT {
+        return Some(Ordering::Less);
+    }
+    if float < -LIMIT {
+        return Some(Ordering::Greater);
+    }
+    let whole = float.trunc();
+    Some(
+        int.cmp(&(whole as i64))
+            .then_with(|| 0.0.partial_cmp(&(float - whole)).unwrap_or(Ordering::Equal)),
+    )
+}
+
+fn overflow(result: Option<i64>) -> Result<i64, &'static str> {
+    result.ok_or("Integer overflow.")
 }
 
 impl Add for Value {
-    type Output = Self;
+    type Output = Result<Self, &'static str>;
 
-    fn add(self, rhs: Self) -> Self {
+    fn add(self, rhs: Self) -> Self::Output {
         match (self, rhs) {
-            (Value::F64(left), Value::F64(right)) => Value::F64(left + right),
             (Value::String(left), Value::String(right)) => {
                 let mut new_string = left;
                 new_string.push_str(&right);
-                Value::String(new_string)
+                Ok(Value::String(new_string))
             }
-            (_, _) => panic!("Not implemented"),
+            (left, right) if left.as_f64().is_some() && right.as_f64().is_some() => left
+                .arithmetic(
+                    right,
+                    |left, right| overflow(left.checked_add(right)),
+                    |left, right| left + right,
+                ),
+            (_, _) => Err("Operands must be two numbers or two strings."),
         }
     }
 }
 
 impl Sub for Value {
-    type Output = Self;
+    type Output = Result<Self, &'static str>;
 
-    fn sub(self, rhs: Self) -> Self {
-        match (self, rhs) {
-            (Value::F64(left), Value::F64(right)) => Value::F64(left - right),
-            (_, _) => panic!("Not implemented"),
-        }
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.arithmetic(
+            rhs,
+            |left, right| overflow(left.checked_sub(right)),
+            |left, right| left - right,
+        )
     }
 }
 
+// Integer division truncates towards zero, dividing an integer by zero is
+// an error while floats give infinity or NaN.
 impl Div for Value {
-    type Output = Self;
+    type Output = Result<Self, &'static str>;
 
-    fn div(self, rhs: Self) -> Self {
-        match (self, rhs) {
-            (Value::F64(left), Value::F64(right)) => Value::F64(left / right),
-            (_, _) => panic!("Not implemented"),
-        }
+    fn div(self, rhs: Self) -> Self::Output {
+        self.arithmetic(
+            rhs,
+            |left, right| match right {
+                0 => Err("Division by zero."),
+                _ => overflow(left.checked_div(right)),
+            },
+            |left, right| left / right,
+        )
     }
 }
 
 impl Mul for Value {
-    type Output = Self;
+    type Output = Result<Self, &'static str>;
 
-    fn mul(self, rhs: Self) -> Self {
-        match (self, rhs) {
-            (Value::F64(left), Value::F64(right)) => Value::F64(left * right),
-            (_, _) => panic!("Not implemented"),
-        }
+    fn mul(self, rhs: Self) -> Self::Output {
+        self.arithmetic(
+            rhs,
+            |left, right| overflow(left.checked_mul(right)),
+            |left, right| left * right,
+        )
+    }
+}
+
+// Like Rust's `%` the result takes the sign of the left operand.
+impl Rem for Value {
+    type Output = Result<Self, &'static str>;
+
+    fn rem(self, rhs: Self) -> Self::Output {
+        self.arithmetic(
+            rhs,
+            |left, right| match right {
+                0 => Err("Division by zero."),
+                _ => overflow(left.checked_rem(right)),
+            },
+            |left, right| left % right,
+        )
+    }
+}
+
+impl BitAnd for Value {
+    type Output = Result<Self, &'static str>;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        let (left, right) = self.integral_operands(rhs)?;
+        Ok(Value::Int(left & right))
+    }
+}
+
+impl BitOr for Value {
+    type Output = Result<Self, &'static str>;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        let (left, right) = self.integral_operands(rhs)?;
+        Ok(Value::Int(left | right))
+    }
+}
+
+impl BitXor for Value {
+    type Output = Result<Self, &'static str>;
+
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        let (left, right) = self.integral_operands(rhs)?;
+        Ok(Value::Int(left ^ right))
+    }
+}
+
+impl Shl for Value {
+    type Output = Result<Self, &'static str>;
+
+    fn shl(self, rhs: Self) -> Self::Output {
+        let (left, right) = self.integral_operands(rhs)?;
+        Ok(Value::Int(left << Value::shift_amount(right)?))
+    }
+}
+
+impl Shr for Value {
+    type Output = Result<Self, &'static str>;
+
+    fn shr(self, rhs: Self) -> Self::Output {
+        let (left, right) = self.integral_operands(rhs)?;
+        Ok(Value::Int(left >> Value::shift_amount(right)?))
     }
 }
 
 impl Neg for Value {
-    type Output = Self;
+    type Output = Result<Self, &'static str>;
 
-    fn neg(self) -> Self {
+    fn neg(self) -> Self::Output {
         match self {
-            Value::F64(value) => Value::F64(-value),
-            _ => panic!("Expected a number, got: {:?}", self),
+            Value::Int(value) => overflow(value.checked_neg()).map(Value::Int),
+            Value::F64(value) => Ok(Value::F64(-value)),
+            _ => Err("Operand must be a number."),
         }
     }
 }
@@ -253,11 +1318,61 @@ impl Not for Value {
     }
 }
 
-mod tests {
+#[cfg(test)]
+pub(crate) mod tests {
     use super::*;
     use crate::lox::expr::Literal as ExprLiteral;
+    use crate::lox::parser::Parser;
+    use crate::lox::resolver::Resolver;
+    use crate::lox::scanner::Scanner;
     use crate::lox::token::{Literal, Token, TokenType};
 
+    // Helpers shared with the tests of the natives.
+    fn parse(source: &str) -> Vec<Stmt> {
+        let mut scanner = Scanner::new(source);
+        let (tokens, _) = scanner.scan_text();
+        let mut parser = Parser::new(&tokens);
+        let (mut statements, errors) = parser.parse();
+        assert_eq!(errors, vec![]);
+        Resolver::new().resolve(&mut statements).unwrap();
+        statements
+    }
+
+    pub(crate) fn interpret(source: &str) -> Interpreter {
+        interpret_with(Interpreter::new(), source)
+    }
+
+    pub(crate) fn interpret_with(mut interpreter: Interpreter, source: &str) -> Interpreter {
+        for stmt in &parse(source) {
+            interpreter.evaluate_node(stmt).unwrap();
+        }
+        interpreter
+    }
+
+    pub(crate) fn runtime_error(source: &str) -> RuntimeError {
+        runtime_error_with(Interpreter::new(), source)
+    }
+
+    pub(crate) fn runtime_error_with(mut interpreter: Interpreter, source: &str) -> RuntimeError {
+        for stmt in &parse(source) {
+            if let Err(error) = interpreter.evaluate_node(stmt) {
+                return error;
+            }
+        }
+        panic!("Expected a runtime error")
+    }
+
+    pub(crate) fn variable(interpreter: &Interpreter, name: &str) -> Value {
+        interpreter.env.borrow().get(name).unwrap()
+    }
+
+    pub(crate) fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rlox-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
     #[test]
     fn literal_string() {
         let mut interpreter = Interpreter::new();
@@ -270,7 +1385,7 @@ mod tests {
             ),
         });
 
-        let value = interpreter.evaluate_expression(expr);
+        let value = interpreter.evaluate_expression(&expr).unwrap();
         assert_eq!(value, Value::String("string".into()));
     }
 
@@ -287,7 +1402,7 @@ mod tests {
             operator,
         });
 
-        let value = interpreter.evaluate_expression(expr);
+        let value = interpreter.evaluate_expression(&expr).unwrap();
         assert_eq!(value, Value::Boolean(false));
     }
 
@@ -308,7 +1423,7 @@ mod tests {
             operator,
         });
 
-        let value = interpreter.evaluate_expression(expr);
+        let value = interpreter.evaluate_expression(&expr).unwrap();
         assert_eq!(value, Value::F64(3.0));
     }
 
@@ -329,7 +1444,7 @@ mod tests {
             operator,
         });
 
-        let value = interpreter.evaluate_expression(expr);
+        let value = interpreter.evaluate_expression(&expr).unwrap();
         assert_eq!(value, Value::Boolean(true))
     }
 
@@ -350,7 +1465,7 @@ mod tests {
             operator,
         });
 
-        let value = interpreter.evaluate_expression(expr);
+        let value = interpreter.evaluate_expression(&expr).unwrap();
         assert_eq!(value, Value::Boolean(false))
     }
 
@@ -371,7 +1486,7 @@ mod tests {
             operator,
         });
 
-        let value = interpreter.evaluate_expression(expr);
+        let value = interpreter.evaluate_expression(&expr).unwrap();
         assert_eq!(value, Value::F64(9.0));
     }
 
@@ -384,12 +1499,13 @@ mod tests {
         let one = Token::new(TokenType::Number, "1".to_owned(), Literal::F64(1.0), 1);
 
         let var_dcl = Stmt::Declaration(Var {
-            name: "a".to_owned(),
+            name: variable.clone(),
             value: Expr::Literal(crate::lox::expr::Literal { token: one.clone() }),
         });
 
         let while_left = Expr::Var(crate::lox::expr::Var {
             name: variable.clone(),
+            depth: None,
         });
         let while_right = Expr::Literal(crate::lox::expr::Literal { token: two.clone() });
         let while_greater_expr = Expr::Binary(Binary {
@@ -400,6 +1516,7 @@ mod tests {
 
         let block_var = Expr::Var(crate::lox::expr::Var {
             name: variable.clone(),
+            depth: None,
         });
         let block_value = Expr::Literal(crate::lox::expr::Literal { token: one.clone() });
         let block_right = Expr::Binary(Binary {
@@ -410,24 +1527,935 @@ mod tests {
         let block_left = Stmt::Expr(Expr::Assignment(super::Assignment {
             name: variable,
             value: Box::new(block_right),
+            depth: None,
         }));
 
-        let block = Stmt::Block(super::Block {
+        let block = Stmt::Block(crate::lox::stmt::Block {
             stmt_vec: vec![block_left],
         });
 
         let while_stmt = Stmt::While(While {
             condition: while_greater_expr,
             body: Box::new(block),
+            increment: None,
         });
 
         let mut interpreter = Interpreter::new();
 
-        interpreter.evaluate_node(var_dcl);
-        interpreter.evaluate_node(while_stmt);
+        interpreter.evaluate_node(&var_dcl).unwrap();
+        interpreter.evaluate_node(&while_stmt).unwrap();
         assert_eq!(
             interpreter.env.borrow_mut().get("a").unwrap(),
             Value::F64(2.0)
         );
     }
+
+    #[test]
+    fn recursive_function() {
+        let source = "
+            fun fib(n) {
+              if (n < 2) return n;
+              return fib(n - 1) + fib(n - 2);
+            }
+            var result = fib(10);
+        ";
+        let interpreter = interpret(source);
+        assert_eq!(
+            interpreter.env.borrow().get("result").unwrap(),
+            Value::Int(55)
+        );
+    }
+
+    #[test]
+    fn runaway_recursion_is_a_runtime_error() {
+        let run = || {
+            let error = runtime_error("fun f(n) {\n  return f(n + 1);\n}\nf(0);");
+            let source = "
+                fun f(n) { return f(n + 1); }
+                var caught;
+                try { f(0); } catch (e) { caught = e.message; }
+                fun count(n) {
+                  if (n == 0) return 0;
+                  return count(n - 1) + 1;
+                }
+                var deepest = count(999);
+            ";
+            let interpreter = interpret(source);
+            (
+                error.line(),
+                error.message,
+                variable(&interpreter, "caught").to_string(),
+                variable(&interpreter, "deepest").to_string(),
+            )
+        };
+        let result = std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(run)
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(
+            result,
+            (
+                2,
+                "Stack overflow.".to_string(),
+                "Stack overflow.".to_string(),
+                "999".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn return_unwinds_loops_and_blocks() {
+        let source = "
+            fun find(limit) {
+              var i = 0;
+              while (true) {
+                {
+                  if (i == limit) return i;
+                }
+                i = i + 1;
+              }
+            }
+            var result = find(3);
+        ";
+        let interpreter = interpret(source);
+        assert_eq!(
+            interpreter.env.borrow().get("result").unwrap(),
+            Value::Int(3)
+        );
+    }
+
+    #[test]
+    fn break_and_continue() {
+        let source = "
+            var total = 0;
+            for (var i = 0; i < 10; i = i + 1) {
+              if (i == 2) continue;
+              if (i == 5) break;
+              total = total + i;
+            }
+            var j = 0;
+            while (true) {
+              j = j + 1;
+              {
+                if (j < 3) continue;
+              }
+              break;
+            }
+        ";
+        let interpreter = interpret(source);
+        assert_eq!(
+            interpreter.env.borrow().get("total").unwrap(),
+            Value::Int(8)
+        );
+        assert_eq!(interpreter.env.borrow().get("j").unwrap(), Value::Int(3));
+    }
+
+    #[test]
+    fn break_only_leaves_the_innermost_loop() {
+        let source = "
+            var count = 0;
+            for (var i = 0; i < 3; i = i + 1) {
+              for (var j = 0; j < 3; j = j + 1) {
+                if (j == 1) break;
+                count = count + 1;
+              }
+            }
+        ";
+        let interpreter = interpret(source);
+        assert_eq!(
+            interpreter.env.borrow().get("count").unwrap(),
+            Value::Int(3)
+        );
+    }
+
+    #[test]
+    fn closures_capture_their_environment() {
+        let source = "
+            fun counter() {
+              var count = 0;
+              fun increment() {
+                count = count + 1;
+                return count;
+              }
+              return increment;
+            }
+            var next = counter();
+            next();
+            var result = next();
+        ";
+        let interpreter = interpret(source);
+        assert_eq!(
+            interpreter.env.borrow().get("result").unwrap(),
+            Value::Int(2)
+        );
+    }
+
+    #[test]
+    fn class_fields_methods_and_initializer() {
+        let source = "
+            class Counter {
+              init(start) {
+                this.count = start;
+              }
+              increment() {
+                this.count = this.count + 1;
+                return this;
+              }
+            }
+            var counter = Counter(5);
+            var result = counter.increment().increment().count;
+        ";
+        let interpreter = interpret(source);
+        assert_eq!(
+            interpreter.env.borrow().get("result").unwrap(),
+            Value::Int(7)
+        );
+    }
+
+    #[test]
+    fn bound_methods_share_the_declaration() {
+        let source = "
+            class Counter {
+              increment() {}
+            }
+            var counter = Counter();
+            var first = counter.increment;
+            var second = counter.increment;
+        ";
+        let interpreter = interpret(source);
+        let env = interpreter.env.borrow();
+        match (env.get("first").unwrap(), env.get("second").unwrap()) {
+            (Value::Function(first), Value::Function(second)) => {
+                assert!(!Rc::ptr_eq(&first, &second));
+                assert!(Rc::ptr_eq(&first.declaration, &second.declaration));
+            }
+            other => panic!("expected bound methods, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn inheritance_and_super_calls() {
+        let source = "
+            class Animal {
+              sound() {
+                return \"...\";
+              }
+              speak() {
+                return this.sound();
+              }
+            }
+            class Dog < Animal {
+              sound() {
+                return \"woof \" + super.sound();
+              }
+            }
+            var result = Dog().speak();
+        ";
+        let interpreter = interpret(source);
+        assert_eq!(
+            interpreter.env.borrow().get("result").unwrap(),
+            Value::String("woof ...".into())
+        );
+    }
+
+    #[test]
+    fn closures_resolve_statically() {
+        let source = "
+            var a = \"global\";
+            var first;
+            var second;
+            {
+              fun show() {
+                return a;
+              }
+              first = show();
+              var a = \"block\";
+              second = show();
+            }
+        ";
+        let interpreter = interpret(source);
+        assert_eq!(
+            interpreter.env.borrow().get("first"),
+            interpreter.env.borrow().get("second")
+        );
+        assert_eq!(
+            interpreter.env.borrow().get("second").unwrap(),
+            Value::String("global".into())
+        );
+    }
+
+    #[test]
+    fn undefined_variable_is_a_runtime_error() {
+        let error = runtime_error("var a = 1;\nprint b;");
+        assert_eq!(error.message, "Undefined variable 'b'.");
+        assert_eq!(error.token.lexeme, "b");
+        assert_eq!(error.line(), 2);
+    }
+
+    #[test]
+    fn operand_type_mismatch_is_a_runtime_error() {
+        let error = runtime_error("var a = 1 + \"one\";");
+        assert_eq!(
+            error.message,
+            "Operands must be two numbers or two strings."
+        );
+        assert_eq!(error.token.t_type, TokenType::Plus);
+
+        let error = runtime_error("var a = -\"one\";");
+        assert_eq!(error.message, "Operand must be a number.");
+    }
+
+    #[test]
+    fn wrong_arity_is_a_runtime_error() {
+        let error = runtime_error("fun f(a) { return a; } f(1, 2);");
+        assert_eq!(error.message, "Expected 1 arguments but got 2.");
+    }
+
+    #[test]
+    fn grouping() {
+        let interpreter = interpret("var result = (1 + 2) * 3;");
+        assert_eq!(
+            interpreter.env.borrow().get("result").unwrap(),
+            Value::Int(9)
+        );
+    }
+
+    #[test]
+    fn runtime_error_spans_whole_expression() {
+        let error = runtime_error("var a = 1;\nvar b = a + \"one\";");
+        assert_eq!(error.span, Span::new(19, 28, 9));
+    }
+
+    #[test]
+    fn list_literals_indexing_and_assignment() {
+        let source = "
+            var xs = [1, 2, [3]];
+            var first = xs[0];
+            var assigned = xs[1] = xs[2][0] + 1;
+        ";
+        let interpreter = interpret(source);
+        let env = interpreter.env.borrow();
+        assert_eq!(env.get("first").unwrap(), Value::Int(1));
+        assert_eq!(env.get("assigned").unwrap(), Value::Int(4));
+        assert_eq!(env.get("xs").unwrap().to_string(), "[1, 4, [3]]");
+    }
+
+    #[test]
+    fn lists_are_shared_by_reference() {
+        let source = "
+            var xs = [1, 2];
+            var ys = xs;
+            fun set(list) { list[0] = \"changed\"; }
+            set(ys);
+            var same = xs == ys;
+            var equal_contents = xs == [\"changed\", 2];
+        ";
+        let interpreter = interpret(source);
+        let env = interpreter.env.borrow();
+        assert_eq!(env.get("xs").unwrap().to_string(), "[changed, 2]");
+        assert_eq!(env.get("same").unwrap(), Value::Boolean(true));
+        assert_eq!(env.get("equal_contents").unwrap(), Value::Boolean(false));
+    }
+
+    #[test]
+    fn self_containing_values_print_and_compare() {
+        let source = "
+            var xs = [1];
+            xs[0] = xs;
+            var m = {\"a\": 1};
+            m[\"a\"] = [m, xs];
+            var ys = [1];
+            ys[0] = ys;
+            assertEqual(xs, xs);
+            assertEqual(xs, ys);
+        ";
+        let interpreter = interpret(source);
+        let env = interpreter.env.borrow();
+        assert_eq!(env.get("xs").unwrap().to_string(), "[[...]]");
+        assert_eq!(env.get("m").unwrap().to_string(), "{a: [{...}, [[...]]]}");
+        assert_eq!(interpreter.assertions_passed(), 2);
+
+        let error = runtime_error("var xs = [1, 2]; xs[0] = xs;\nassertEqual(xs, [[1], 2]);");
+        assert_eq!(error.message, "Expected [[1], 2] but got [[...], 2].");
+    }
+
+    #[test]
+    fn bad_list_indices_are_runtime_errors() {
+        let error = runtime_error("var xs = [1, 2];\nprint xs[2];");
+        assert_eq!(error.message, "List index out of bounds.");
+        assert_eq!(error.line(), 2);
+
+        let error = runtime_error("var xs = [1, 2]; xs[-1] = 0;");
+        assert_eq!(error.message, "List index out of bounds.");
+
+        let error = runtime_error("var xs = [1, 2]; print xs[0.5];");
+        assert_eq!(error.message, "List index must be an integer.");
+
+        let error = runtime_error("var x = 1; print x[0];");
+        assert_eq!(error.message, "Only lists and maps can be indexed.");
+    }
+
+    #[test]
+    fn map_literals_lookup_and_assignment() {
+        let source = "
+            var config = {\"name\": \"lox\", 1: true, false: 0};
+            var name = config[\"name\"];
+            config[\"name\"] = \"rlox\";
+            config[-0] = \"zero\";
+            var zero = config[0];
+            { var block = 1; }
+        ";
+        let interpreter = interpret(source);
+        let env = interpreter.env.borrow();
+        assert_eq!(env.get("name").unwrap(), Value::String("lox".into()));
+        assert_eq!(env.get("zero").unwrap(), Value::String("zero".into()));
+        assert_eq!(
+            env.get("config").unwrap().to_string(),
+            "{false: 0, 0: zero, 1: true, name: rlox}"
+        );
+    }
+
+    #[test]
+    fn map_natives() {
+        let source = "
+            var m = {\"b\": 2, \"a\": 1};
+            var ks = keys(m);
+            var vs = values(m);
+            var had = has(m, \"a\");
+            var removed = remove(m, \"a\");
+            var missing = remove(m, \"a\");
+            var has_after = has(m, \"a\");
+        ";
+        let interpreter = interpret(source);
+        let env = interpreter.env.borrow();
+        assert_eq!(env.get("ks").unwrap().to_string(), "[a, b]");
+        assert_eq!(env.get("vs").unwrap().to_string(), "[1, 2]");
+        assert_eq!(env.get("had").unwrap(), Value::Boolean(true));
+        assert_eq!(env.get("removed").unwrap(), Value::Int(1));
+        assert_eq!(env.get("missing").unwrap(), Value::Nil);
+        assert_eq!(env.get("has_after").unwrap(), Value::Boolean(false));
+    }
+
+    #[test]
+    fn bad_map_keys_are_runtime_errors() {
+        let error = runtime_error("var m = {};\nprint m[\"a\"];");
+        assert_eq!(error.message, "Undefined key 'a'.");
+        assert_eq!(error.line(), 2);
+
+        let error = runtime_error("var m = {[1]: 2};");
+        assert_eq!(
+            error.message,
+            "Map keys must be strings, numbers or booleans."
+        );
+
+        let error = runtime_error("print has([], 1);");
+        assert_eq!(error.message, "Expected a map as the first argument.");
+    }
+
+    #[test]
+    fn string_interpolation() {
+        let source = r#"
+            var name = "lox";
+            var xs = [1, 2];
+            var text = "${name} has ${xs[0] + xs[1]} items: ${xs}, \"${"nested ${true}"}\"";
+        "#;
+        let interpreter = interpret(source);
+        assert_eq!(
+            interpreter.env.borrow().get("text").unwrap(),
+            Value::String("lox has 3 items: [1, 2], \"nested true\"".into())
+        );
+    }
+
+    #[test]
+    fn modulo_exponent_and_bitwise_operators() {
+        let source = "
+            var modulo = -7 % 3;
+            var power = -2 ** 2;
+            var right_assoc = 2 ** 3 ** 2;
+            var inverse = 2 ** -1;
+            var mixed = 1 | 6 & 3 ^ 8;
+            var shifts = 1 << 4 >> 2;
+            var shift_then_add = 1 << 1 + 1;
+            var masked = 6 & 3 == 2;
+            var not = ~5;
+            var product = 7 % 4 * 2;
+        ";
+        let interpreter = interpret(source);
+        let env = interpreter.env.borrow();
+        assert_eq!(env.get("modulo").unwrap(), Value::Int(-1));
+        assert_eq!(env.get("power").unwrap(), Value::Int(-4));
+        assert_eq!(env.get("right_assoc").unwrap(), Value::Int(512));
+        assert_eq!(env.get("inverse").unwrap(), Value::F64(0.5));
+        assert_eq!(env.get("mixed").unwrap(), Value::Int(11));
+        assert_eq!(env.get("shifts").unwrap(), Value::Int(4));
+        assert_eq!(env.get("shift_then_add").unwrap(), Value::Int(4));
+        assert_eq!(env.get("masked").unwrap(), Value::Boolean(true));
+        assert_eq!(env.get("not").unwrap(), Value::Int(-6));
+        assert_eq!(env.get("product").unwrap(), Value::Int(6));
+    }
+
+    #[test]
+    fn bitwise_operators_need_integers() {
+        let error = runtime_error("print 1.5 & 1;");
+        assert_eq!(error.message, "Operands must be integers.");
+        assert_eq!(error.token.t_type, TokenType::Ampersand);
+
+        let error = runtime_error("print ~\"a\";");
+        assert_eq!(error.message, "Operand must be an integer.");
+
+        let error = runtime_error("print 1 << 64;");
+        assert_eq!(error.message, "Shift amount must be between 0 and 63.");
+
+        let error = runtime_error("print \"a\" % 2;");
+        assert_eq!(error.message, "Operands must be numbers.");
+    }
+
+    #[test]
+    fn compound_assignment_and_increments() {
+        let source = "
+            var a = 10;
+            a += 5;
+            a -= 3;
+            a *= 2;
+            a /= 4;
+            var b = 1;
+            var post = b++;
+            var pre = ++b;
+            var down = b--;
+            var text = \"a\";
+            text += \"b\";
+            var chained = a += 1;
+        ";
+        let interpreter = interpret(source);
+        let env = interpreter.env.borrow();
+        assert_eq!(env.get("a").unwrap(), Value::Int(7));
+        assert_eq!(env.get("chained").unwrap(), Value::Int(7));
+        assert_eq!(env.get("post").unwrap(), Value::Int(1));
+        assert_eq!(env.get("pre").unwrap(), Value::Int(3));
+        assert_eq!(env.get("down").unwrap(), Value::Int(3));
+        assert_eq!(env.get("b").unwrap(), Value::Int(2));
+        assert_eq!(env.get("text").unwrap(), Value::String("ab".into()));
+    }
+
+    #[test]
+    fn update_targets_are_evaluated_once() {
+        let source = "
+            var calls = 0;
+            var xs = [1, 2];
+            fun index() { calls++; return 1; }
+            xs[index()] += 10;
+            xs[index()]++;
+            class Box { init() { this.value = 1; } }
+            var boxes = [Box()];
+            fun first() { calls++; return boxes[0]; }
+            first().value *= 3;
+            var old = first().value--;
+            {
+              var local = 1;
+              local += 1;
+              calls += local;
+            }
+        ";
+        let interpreter = interpret(source);
+        let env = interpreter.env.borrow();
+        assert_eq!(env.get("xs").unwrap().to_string(), "[1, 13]");
+        assert_eq!(env.get("old").unwrap(), Value::Int(3));
+        assert_eq!(env.get("calls").unwrap(), Value::Int(6));
+    }
+
+    #[test]
+    fn update_errors() {
+        let error = runtime_error("var a = \"a\"; a -= 1;");
+        assert_eq!(error.message, "Operands must be numbers.");
+        assert_eq!(error.token.t_type, TokenType::MinusEqual);
+
+        let error = runtime_error("undefined++;");
+        assert_eq!(error.message, "Undefined variable 'undefined'.");
+    }
+
+    #[test]
+    fn ternary_only_evaluates_the_chosen_branch() {
+        let source = "
+            var calls = 0;
+            fun touch(value) { calls++; return value; }
+            var yes = true ? touch(1) : touch(2);
+            var no = nil ? touch(1) : touch(2);
+            var nested = false ? 1 : 0 ? 2 : 3;
+            var assigned;
+            var result = yes == 1 ? assigned = \"then\" : \"else\";
+            var low = 1 < 2 or false ? \"low\" : \"high\";
+        ";
+        let interpreter = interpret(source);
+        let env = interpreter.env.borrow();
+        assert_eq!(env.get("calls").unwrap(), Value::Int(2));
+        assert_eq!(env.get("yes").unwrap(), Value::Int(1));
+        assert_eq!(env.get("no").unwrap(), Value::Int(2));
+        assert_eq!(env.get("nested").unwrap(), Value::Int(2));
+        assert_eq!(env.get("assigned").unwrap(), Value::String("then".into()));
+        assert_eq!(env.get("result").unwrap(), Value::String("then".into()));
+        assert_eq!(env.get("low").unwrap(), Value::String("low".into()));
+    }
+
+    #[test]
+    fn integers_and_promotion_to_float() {
+        let source = "
+            var big = 9007199254740993 + 0;
+            var promoted = 1 + 0.5;
+            var whole_float = 2.0 * 3;
+            var same = 1 == 1.0;
+            var less = 1 < 1.5;
+            var power = 2 ** 62;
+            var index = [1, 2][1.0];
+        ";
+        let interpreter = interpret(source);
+        let env = interpreter.env.borrow();
+        assert_eq!(env.get("big").unwrap(), Value::Int(9_007_199_254_740_993));
+        assert_eq!(env.get("promoted").unwrap(), Value::F64(1.5));
+        assert_eq!(env.get("whole_float").unwrap(), Value::F64(6.0));
+        assert_eq!(env.get("same").unwrap(), Value::Boolean(true));
+        assert_eq!(env.get("less").unwrap(), Value::Boolean(true));
+        assert_eq!(env.get("power").unwrap(), Value::Int(1 << 62));
+        assert_eq!(env.get("index").unwrap(), Value::Int(2));
+    }
+
+    // Integer division truncates towards zero and `%` keeps the sign of the
+    // dividend, so `a == (a / b) * b + a % b` always holds. Dividing an
+    // integer by zero is an error, floats follow IEEE 754.
+    #[test]
+    fn integer_division() {
+        let source = "
+            var quotient = 7 / 2;
+            var negative = -7 / 2;
+            var remainder = -7 % 2;
+            var float = 7 / 2.0;
+            var infinite = 1.0 / 0;
+        ";
+        let interpreter = interpret(source);
+        let env = interpreter.env.borrow();
+        assert_eq!(env.get("quotient").unwrap(), Value::Int(3));
+        assert_eq!(env.get("negative").unwrap(), Value::Int(-3));
+        assert_eq!(env.get("remainder").unwrap(), Value::Int(-1));
+        assert_eq!(env.get("float").unwrap(), Value::F64(3.5));
+        assert_eq!(env.get("infinite").unwrap(), Value::F64(f64::INFINITY));
+
+        let error = runtime_error("print 1 / 0;");
+        assert_eq!(error.message, "Division by zero.");
+        let error = runtime_error("print 1 % 0;");
+        assert_eq!(error.message, "Division by zero.");
+    }
+
+    #[test]
+    fn integer_overflow_is_a_runtime_error() {
+        for source in &[
+            "print 9223372036854775807 + 1;",
+            "print -9223372036854775807 - 2;",
+            "print 4294967296 * 4294967296;",
+            "print 2 ** 63;",
+            "var min = -9223372036854775807 - 1; print -min;",
+            "var min = -9223372036854775807 - 1; print min / -1;",
+            "var a = 9223372036854775807; a++;",
+        ] {
+            assert_eq!(runtime_error(source).message, "Integer overflow.");
+        }
+    }
+
+    #[test]
+    fn comparisons_with_nan_are_false() {
+        let source = "
+            var nan = 0.0 / 0.0;
+            var results = [nan < 1, nan <= 1, nan > 1, nan >= 1, nan == nan, 1 >= nan];
+        ";
+        let interpreter = interpret(source);
+        assert_eq!(
+            interpreter.env.borrow().get("results").unwrap().to_string(),
+            "[false, false, false, false, false, false]"
+        );
+    }
+
+    #[test]
+    fn integers_and_floats_compare_exactly() {
+        let source = "
+            var equal = 9007199254740993 == 9007199254740992.0;
+            var greater = 9007199254740993 > 9007199254740992.0;
+            var less = 9007199254740992.0 < 9007199254740993;
+            var fraction = [2 < 2.5, -2 > -2.5, 3 == 3.0, 3 <= 3.0];
+            var huge = 9223372036854775807 < 9223372036854775808.0;
+        ";
+        let interpreter = interpret(source);
+        let env = interpreter.env.borrow();
+        assert_eq!(env.get("equal").unwrap(), Value::Boolean(false));
+        assert_eq!(env.get("greater").unwrap(), Value::Boolean(true));
+        assert_eq!(env.get("less").unwrap(), Value::Boolean(true));
+        assert_eq!(
+            env.get("fraction").unwrap().to_string(),
+            "[true, true, true, true]"
+        );
+        assert_eq!(env.get("huge").unwrap(), Value::Boolean(true));
+    }
+
+    #[test]
+    fn thrown_values_and_runtime_errors_are_caught() {
+        let source = "
+            var thrown;
+            try {
+              throw {\"code\": 42};
+            } catch (e) {
+              thrown = e[\"code\"];
+            }
+            var message;
+            var line;
+            try {
+              var x = 1;
+              print x
+                + \"a\";
+            } catch (e) {
+              message = e.message;
+              line = e.line;
+            }
+        ";
+        let interpreter = interpret(source);
+        let env = interpreter.env.borrow();
+        assert_eq!(env.get("thrown").unwrap(), Value::Int(42));
+        assert_eq!(
+            env.get("message").unwrap(),
+            Value::String("Operands must be two numbers or two strings.".into())
+        );
+        assert_eq!(env.get("line").unwrap(), Value::Int(13));
+    }
+
+    #[test]
+    fn finally_runs_on_every_exit() {
+        let source = "
+            var log = \"\";
+            fun f() {
+              try {
+                return 1;
+              } finally {
+                log = log + \"r\";
+              }
+            }
+            var result = f();
+            while (true) {
+              try {
+                break;
+              } finally {
+                log = log + \"b\";
+              }
+            }
+            try {
+              try {
+                throw \"inner\";
+              } finally {
+                log = log + \"t\";
+              }
+            } catch (e) {
+              log = log + e;
+            }
+            fun g() {
+              try {
+                return 1;
+              } finally {
+                return 2;
+              }
+            }
+            var overridden = g();
+        ";
+        let interpreter = interpret(source);
+        let env = interpreter.env.borrow();
+        assert_eq!(env.get("result").unwrap(), Value::Int(1));
+        assert_eq!(env.get("log").unwrap(), Value::String("rbtinner".into()));
+        assert_eq!(env.get("overridden").unwrap(), Value::Int(2));
+    }
+
+    #[test]
+    fn unwinding_restores_the_environment() {
+        let source = "
+            var a = \"global\";
+            fun fail() {
+              var a = \"function\";
+              { var a = \"block\"; throw a; }
+            }
+            var caught;
+            try {
+              var a = \"try\";
+              { fail(); }
+            } catch (e) {
+              caught = e;
+            }
+            var after = a;
+        ";
+        let interpreter = interpret(source);
+        let env = interpreter.env.borrow();
+        assert_eq!(env.get("caught").unwrap(), Value::String("block".into()));
+        assert_eq!(env.get("after").unwrap(), Value::String("global".into()));
+        assert!(Rc::ptr_eq(&interpreter.env, &interpreter.globals));
+    }
+
+    #[test]
+    fn uncaught_throw_is_a_runtime_error() {
+        let error = runtime_error("try { throw 1; } catch (e) { throw e + 1; }");
+        assert_eq!(error.message, "Uncaught exception: 2");
+        assert_eq!(error.thrown, Some(Value::Int(2)));
+    }
+
+    #[test]
+    fn deferred_statements_run_in_reverse_on_block_exit() {
+        let source = "
+            var log = \"\";
+            {
+              var name = \"a\";
+              defer log = log + name;
+              defer { log = log + \"b\"; }
+              log = log + \"c\";
+            }
+            fun f() {
+              defer log = log + \"r\";
+              return 1;
+            }
+            var result = f();
+            for (var i = 0; i < 3; i = i + 1) {
+              defer log = log + \"${i}\";
+              if (i == 1) break;
+            }
+            try {
+              defer log = log + \"e\";
+              print nil + 1;
+            } catch (e) {}
+        ";
+        let interpreter = interpret(source);
+        let env = interpreter.env.borrow();
+        assert_eq!(env.get("log").unwrap(), Value::String("cbar01e".into()));
+        assert_eq!(env.get("result").unwrap(), Value::Int(1));
+    }
+
+    #[test]
+    fn deferred_errors_replace_the_block_result() {
+        let error = runtime_error("{ defer print nil + 1; throw \"first\"; }");
+        assert_eq!(error.thrown, None);
+        assert_eq!(
+            error.message,
+            "Operands must be two numbers or two strings."
+        );
+    }
+
+    #[test]
+    fn registered_natives_are_callable() {
+        let mut interpreter = Interpreter::empty();
+        assert_eq!(interpreter.globals.borrow().get("clock"), None);
+
+        let calls = Rc::new(RefCell::new(0));
+        let counter = Rc::clone(&calls);
+        interpreter.register_native(NativeFunction::new("add", 2, move |arguments| {
+            *counter.borrow_mut() += 1;
+            Ok((arguments[0].clone() + arguments[1].clone())?)
+        }));
+
+        let interpreter = interpret_with(interpreter, "var sum = add(1, add(2, 3));");
+        assert_eq!(variable(&interpreter, "sum"), Value::Int(6));
+        assert_eq!(*calls.borrow(), 2);
+    }
+
+    #[test]
+    fn modules_run_once_in_their_own_environment() {
+        let dir = temp_dir("modules");
+        std::fs::create_dir(dir.join("lib")).unwrap();
+        std::fs::write(dir.join("lib/helper.lox"), "var step = 2;").unwrap();
+        std::fs::write(
+            dir.join("lib/counter.lox"),
+            "import \"helper.lox\";
+             var count = 0;
+             fun next() { count = count + helper.step; return count; }",
+        )
+        .unwrap();
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_script(&dir.join("main.lox"));
+        let source = "
+            var count = 100;
+            import \"lib/counter.lox\";
+            import \"lib/counter.lox\" as again;
+            var first = counter.next();
+            var second = again.next();
+            var shared = counter.count;
+        ";
+        let interpreter = interpret_with(interpreter, source);
+        let env = interpreter.env.borrow();
+        assert_eq!(env.get("first").unwrap(), Value::Int(2));
+        assert_eq!(env.get("second").unwrap(), Value::Int(4));
+        assert_eq!(env.get("shared").unwrap(), Value::Int(4));
+        assert_eq!(env.get("count").unwrap(), Value::Int(100));
+        assert_eq!(env.get("counter").unwrap().to_string(), "<module counter>");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn import_errors() {
+        let dir = temp_dir("import-errors");
+        std::fs::write(dir.join("a.lox"), "import \"b.lox\";").unwrap();
+        std::fs::write(dir.join("b.lox"), "import \"a.lox\";").unwrap();
+        std::fs::write(dir.join("broken.lox"), "var x = ;").unwrap();
+        std::fs::write(dir.join("throws.lox"), "\nthrow \"boom\";").unwrap();
+        std::fs::write(
+            dir.join("calls.lox"),
+            "fun boom() {\n  return nil + 1;\n}\nfun call(f) { f(); }",
+        )
+        .unwrap();
+
+        let run = |source: &str| {
+            let mut interpreter = Interpreter::new();
+            interpreter.set_script(&dir.join("main.lox"));
+            runtime_error_with(interpreter, source)
+        };
+
+        assert_eq!(
+            run("import \"a.lox\";").message,
+            "Import cycle: a.lox -> b.lox -> a.lox.\n[line 1] in b.lox\n[line 1] in a.lox"
+        );
+        assert_eq!(
+            run("import \"broken.lox\";").message,
+            "Can't import 'broken.lox':\n[line 1] Error at ';': Expect expression."
+        );
+        // Errors from functions declared in a module point into that file.
+        let error = run("import \"calls.lox\";\ncalls.boom();");
+        assert_eq!(error.line(), 2);
+        assert_eq!(
+            error.file.as_deref(),
+            Some(
+                std::fs::canonicalize(dir.join("calls.lox"))
+                    .unwrap()
+                    .as_path()
+            )
+        );
+        let error = run("import \"calls.lox\";\nfun f() {\n\n  nil + 1; }\ncalls.call(f);");
+        assert_eq!((error.line(), error.file), (4, None));
+        let thrown = run("try { import \"throws.lox\"; } catch (e) { throw e + \"!\"; }");
+        assert_eq!(thrown.thrown, Some(Value::String("boom!".into())));
+        let outside = temp_dir("import-outside");
+        std::fs::write(outside.join("secret.lox"), "var secret = 1;").unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.sandbox(dir.clone());
+        interpreter.set_script(&dir.join("main.lox"));
+        let source = format!(
+            "var message; try {{ import \"../{}/secret.lox\"; }} catch (e) {{ message = e.message; }}",
+            outside.file_name().unwrap().to_string_lossy()
+        );
+        let interpreter = interpret_with(interpreter, &source);
+        assert_eq!(
+            interpreter.env.borrow().get("message").unwrap(),
+            Value::String(format!(
+                "Can't import '../{}/secret.lox': Path '{}' is outside of the sandbox.",
+                outside.file_name().unwrap().to_string_lossy(),
+                std::fs::canonicalize(outside.join("secret.lox"))
+                    .unwrap()
+                    .display()
+            ))
+        );
+        std::fs::remove_dir_all(outside).unwrap();
+        assert!(run("import \"missing.lox\";")
+            .message
+            .starts_with("Can't import 'missing.lox': "));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
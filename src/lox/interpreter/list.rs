@@ -0,0 +1,91 @@
+use super::interpreter::Value;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::rc::Rc;
+
+pub struct List {
+    pub elements: Vec<Value>,
+}
+
+impl List {
+    pub fn new(elements: Vec<Value>) -> Self {
+        Self { elements }
+    }
+
+    // Indices have to be whole numbers inside the list, the error is left for
+    // the caller to attach to a token.
+    pub fn position(&self, index: &Value) -> Result<usize, &'static str> {
+        let index = match *index {
+            Value::Int(index) => index,
+            Value::F64(index) if index.fract() == 0.0 => index as i64,
+            _ => return Err("List index must be an integer."),
+        };
+        match usize::try_from(index) {
+            Ok(index) if index < self.elements.len() => Ok(index),
+            _ => Err("List index out of bounds."),
+        }
+    }
+}
+
+pub fn new_list(elements: Vec<Value>) -> Value {
+    Value::List(Rc::new(RefCell::new(List::new(elements))))
+}
+
+thread_local! {
+    // The lists and maps currently being printed, innermost last.
+    static PRINTING: RefCell<Vec<*const ()>> = const { RefCell::new(vec![]) };
+}
+
+// Lists and maps can end up containing themselves, so one that is already
+// being printed further out is written as `placeholder` instead.
+pub fn print_once(
+    value: *const (),
+    f: &mut fmt::Formatter,
+    placeholder: &str,
+    print: impl FnOnce(&mut fmt::Formatter) -> fmt::Result,
+) -> fmt::Result {
+    if PRINTING.with(|printing| printing.borrow().contains(&value)) {
+        return write!(f, "{}", placeholder);
+    }
+    PRINTING.with(|printing| printing.borrow_mut().push(value));
+    let result = print(f);
+    PRINTING.with(|printing| printing.borrow_mut().pop());
+    result
+}
+
+// Lists are shared by reference, so like instances two lists are only equal
+// when they are the same list.
+impl PartialEq for List {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl PartialOrd for List {
+    fn partial_cmp(&self, _other: &Self) -> Option<Ordering> {
+        None
+    }
+}
+
+impl fmt::Debug for List {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "List({})", self.elements.len())
+    }
+}
+
+impl fmt::Display for List {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        print_once(self as *const Self as *const (), f, "[...]", |f| {
+            write!(f, "[")?;
+            for (i, element) in self.elements.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", element)?;
+            }
+            write!(f, "]")
+        })
+    }
+}
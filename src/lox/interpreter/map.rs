@@ -0,0 +1,184 @@
+use super::interpreter::Value;
+use super::list::{new_list, print_once};
+use super::native::{expected, NativeFunction};
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+
+// Only values with a stable notion of equality can be keys. Keys are kept
+// sorted so iterating a map always gives the same order.
+#[derive(Debug, Clone)]
+pub enum Key {
+    Boolean(bool),
+    Int(i64),
+    Number(f64),
+    String(String),
+}
+
+impl Key {
+    pub fn from_value(value: &Value) -> Result<Self, &'static str> {
+        match value {
+            Value::Boolean(boolean) => Ok(Key::Boolean(*boolean)),
+            Value::Int(int) => Ok(Key::Int(*int)),
+            Value::F64(number) if number.is_nan() => Err("Map keys can't be NaN."),
+            // Numbers that are equal have to be the same key, so whole floats
+            // become integers (which also merges -0 and 0).
+            Value::F64(number)
+                if number.fract() == 0.0
+                    && *number >= i64::MIN as f64
+                    && *number < i64::MAX as f64 =>
+            {
+                Ok(Key::Int(*number as i64))
+            }
+            Value::F64(number) => Ok(Key::Number(*number)),
+            Value::String(string) => Ok(Key::String(string.clone())),
+            _ => Err("Map keys must be strings, numbers or booleans."),
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        match self {
+            Key::Boolean(boolean) => Value::Boolean(*boolean),
+            Key::Int(int) => Value::Int(*int),
+            Key::Number(number) => Value::F64(*number),
+            Key::String(string) => Value::String(string.clone()),
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            Key::Boolean(_) => 0,
+            Key::Int(_) | Key::Number(_) => 1,
+            Key::String(_) => 2,
+        }
+    }
+}
+
+impl PartialEq for Key {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Key {}
+
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Key {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Key::Boolean(left), Key::Boolean(right)) => left.cmp(right),
+            (Key::Int(left), Key::Int(right)) => left.cmp(right),
+            (Key::Number(left), Key::Number(right)) => left.total_cmp(right),
+            // Never equal since whole numbers are always stored as Int, the
+            // tie break only keeps the ordering total.
+            (Key::Int(left), Key::Number(right)) => {
+                (*left as f64).total_cmp(right).then(Ordering::Less)
+            }
+            (Key::Number(left), Key::Int(right)) => {
+                left.total_cmp(&(*right as f64)).then(Ordering::Greater)
+            }
+            (Key::String(left), Key::String(right)) => left.cmp(right),
+            (_, _) => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_value())
+    }
+}
+
+pub struct Map {
+    pub entries: BTreeMap<Key, Value>,
+}
+
+impl Map {
+    pub fn new(entries: BTreeMap<Key, Value>) -> Self {
+        Self { entries }
+    }
+}
+
+pub fn natives() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction::new("keys", 1, keys),
+        NativeFunction::new("values", 1, values),
+        NativeFunction::new("has", 2, has),
+        NativeFunction::new("remove", 2, remove),
+    ]
+}
+
+fn map_argument(value: &Value) -> Result<Rc<RefCell<Map>>, String> {
+    match value {
+        Value::Map(map) => Ok(Rc::clone(map)),
+        _ => Err(expected("a map", 0)),
+    }
+}
+
+fn keys(arguments: &[Value]) -> Result<Value, String> {
+    let map = map_argument(&arguments[0])?;
+    let keys = map.borrow().entries.keys().map(Key::to_value).collect();
+    Ok(new_list(keys))
+}
+
+fn values(arguments: &[Value]) -> Result<Value, String> {
+    let map = map_argument(&arguments[0])?;
+    let values = map.borrow().entries.values().cloned().collect();
+    Ok(new_list(values))
+}
+
+fn has(arguments: &[Value]) -> Result<Value, String> {
+    let map = map_argument(&arguments[0])?;
+    let key = Key::from_value(&arguments[1])?;
+    let has = map.borrow().entries.contains_key(&key);
+    Ok(Value::Boolean(has))
+}
+
+// Returns the removed value, or nil when the key wasn't there.
+fn remove(arguments: &[Value]) -> Result<Value, String> {
+    let map = map_argument(&arguments[0])?;
+    let key = Key::from_value(&arguments[1])?;
+    let removed = map.borrow_mut().entries.remove(&key);
+    Ok(removed.unwrap_or(Value::Nil))
+}
+
+// Maps are shared by reference the same way lists are.
+impl PartialEq for Map {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl PartialOrd for Map {
+    fn partial_cmp(&self, _other: &Self) -> Option<Ordering> {
+        None
+    }
+}
+
+impl fmt::Debug for Map {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Map({})", self.entries.len())
+    }
+}
+
+impl fmt::Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        print_once(self as *const Self as *const (), f, "{...}", |f| {
+            write!(f, "{{")?;
+            for (i, (key, value)) in self.entries.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}: {}", key, value)?;
+            }
+            write!(f, "}}")
+        })
+    }
+}
@@ -0,0 +1,213 @@
+use super::interpreter::Value;
+use super::native::{integer_argument, number_argument, NativeFunction};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// SplitMix64, small and good enough for scripts. The same seed always gives
+// the same sequence, which is what `--seed` relies on.
+#[derive(Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn from_time() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or(0);
+        Self::new(nanos)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, 1).
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+pub fn constants() -> Vec<(&'static str, Value)> {
+    vec![
+        ("PI", Value::F64(std::f64::consts::PI)),
+        ("E", Value::F64(std::f64::consts::E)),
+    ]
+}
+
+pub fn natives(rng: &Rc<RefCell<Rng>>) -> Vec<NativeFunction> {
+    let random_rng = Rc::clone(rng);
+    let random_int_rng = Rc::clone(rng);
+    vec![
+        unary("sqrt", f64::sqrt),
+        unary("sin", f64::sin),
+        unary("cos", f64::cos),
+        unary("tan", f64::tan),
+        unary("asin", f64::asin),
+        unary("acos", f64::acos),
+        unary("atan", f64::atan),
+        rounding("floor", f64::floor),
+        rounding("ceil", f64::ceil),
+        rounding("round", f64::round),
+        NativeFunction::new("atan2", 2, atan2),
+        NativeFunction::new("pow", 2, pow),
+        NativeFunction::new("abs", 1, abs),
+        NativeFunction::new("min", 2, |arguments| pick(arguments, |a, b| a <= b)),
+        NativeFunction::new("max", 2, |arguments| pick(arguments, |a, b| a >= b)),
+        NativeFunction::new("random", 0, move |_| {
+            Ok(Value::F64(random_rng.borrow_mut().next_f64()))
+        }),
+        NativeFunction::new("randomInt", 2, move |arguments| {
+            random_int(&mut random_int_rng.borrow_mut(), arguments)
+        }),
+    ]
+}
+
+fn unary(name: &str, function: fn(f64) -> f64) -> NativeFunction {
+    NativeFunction::new(name, 1, move |arguments| {
+        Ok(Value::F64(function(number_argument(arguments, 0)?)))
+    })
+}
+
+// Integers are already whole, so they are returned unchanged.
+fn rounding(name: &str, function: fn(f64) -> f64) -> NativeFunction {
+    NativeFunction::new(name, 1, move |arguments| match arguments[0] {
+        Value::Int(int) => Ok(Value::Int(int)),
+        _ => Ok(Value::F64(function(number_argument(arguments, 0)?))),
+    })
+}
+
+fn atan2(arguments: &[Value]) -> Result<Value, String> {
+    let y = number_argument(arguments, 0)?;
+    let x = number_argument(arguments, 1)?;
+    Ok(Value::F64(y.atan2(x)))
+}
+
+// Same rules as `**`.
+fn pow(arguments: &[Value]) -> Result<Value, String> {
+    number_argument(arguments, 0)?;
+    number_argument(arguments, 1)?;
+    let result = arguments[0].clone().pow(arguments[1].clone())?;
+    Ok(result)
+}
+
+fn abs(arguments: &[Value]) -> Result<Value, String> {
+    match arguments[0] {
+        Value::Int(int) => int
+            .checked_abs()
+            .map(Value::Int)
+            .ok_or_else(|| "Integer overflow.".into()),
+        _ => Ok(Value::F64(number_argument(arguments, 0)?.abs())),
+    }
+}
+
+// Returns one of the arguments as is, so integers stay integers.
+fn pick(arguments: &[Value], first: fn(f64, f64) -> bool) -> Result<Value, String> {
+    let a = number_argument(arguments, 0)?;
+    let b = number_argument(arguments, 1)?;
+    if first(a, b) {
+        Ok(arguments[0].clone())
+    } else {
+        Ok(arguments[1].clone())
+    }
+}
+
+// Both bounds are inclusive.
+fn random_int(rng: &mut Rng, arguments: &[Value]) -> Result<Value, String> {
+    let min = integer_argument(arguments, 0)?;
+    let max = integer_argument(arguments, 1)?;
+    if min > max {
+        return Err("Random range is empty.".into());
+    }
+    let range = (max as i128 - min as i128 + 1) as u128;
+    let offset = (u128::from(rng.next_u64()) * range) >> 64;
+    Ok(Value::Int((min as i128 + offset as i128) as i64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lox::interpreter::interpreter::tests::{
+        interpret, interpret_with, runtime_error, variable,
+    };
+    use crate::lox::interpreter::interpreter::Interpreter;
+
+    #[test]
+    fn math_natives() {
+        let source = "
+            var root = sqrt(16);
+            var power = pow(2, 10);
+            var fractional = pow(4, 0.5);
+            var rounded = [floor(2.5), ceil(2.5), round(2.5), floor(-3)];
+            var absolute = [abs(-3), abs(-1.5)];
+            var smallest = min(3, 1.5);
+            var largest = max(3, 1.5);
+            var angle = atan2(1, 1) * 4;
+            var sine = sin(PI / 2);
+        ";
+        let interpreter = interpret(source);
+        assert_eq!(variable(&interpreter, "root"), Value::F64(4.0));
+        assert_eq!(variable(&interpreter, "power"), Value::Int(1024));
+        assert_eq!(variable(&interpreter, "fractional"), Value::F64(2.0));
+        assert_eq!(
+            variable(&interpreter, "rounded").to_string(),
+            "[2, 3, 3, -3]"
+        );
+        assert_eq!(variable(&interpreter, "absolute").to_string(), "[3, 1.5]");
+        assert_eq!(variable(&interpreter, "smallest"), Value::F64(1.5));
+        assert_eq!(variable(&interpreter, "largest"), Value::Int(3));
+        assert_eq!(
+            variable(&interpreter, "angle"),
+            Value::F64(std::f64::consts::PI)
+        );
+        assert_eq!(variable(&interpreter, "sine"), Value::F64(1.0));
+
+        assert_eq!(
+            runtime_error("sqrt(\"4\");").message,
+            "Expected a number as the first argument."
+        );
+        assert_eq!(
+            runtime_error("randomInt(2, 1);").message,
+            "Random range is empty."
+        );
+    }
+
+    #[test]
+    fn seeded_random_is_deterministic() {
+        let run = |seed| {
+            let mut interpreter = Interpreter::new();
+            interpreter.seed(seed);
+            let source = "var a = random(); var b = randomInt(-3, 3); var c = randomInt(-3, 3);";
+            let interpreter = interpret_with(interpreter, source);
+            (
+                variable(&interpreter, "a"),
+                variable(&interpreter, "b"),
+                variable(&interpreter, "c"),
+            )
+        };
+        assert_eq!(run(7), run(7));
+        assert_ne!(run(7), run(8));
+
+        let (a, b, c) = run(7);
+        match a {
+            Value::F64(a) => assert!((0.0..1.0).contains(&a)),
+            value => panic!("Expected a number, got {:?}", value),
+        }
+        for value in &[b, c] {
+            match value {
+                Value::Int(int) => assert!((-3..=3).contains(int)),
+                value => panic!("Expected an integer, got {:?}", value),
+            }
+        }
+    }
+}
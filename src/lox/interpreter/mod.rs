@@ -1,2 +1,15 @@
+mod assert;
+mod class;
 mod environment;
+mod file;
+mod function;
+mod input;
+#[allow(clippy::module_inception)]
 pub mod interpreter;
+mod list;
+mod map;
+mod math;
+mod module;
+pub mod native;
+mod prelude;
+mod string;
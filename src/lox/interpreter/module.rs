@@ -0,0 +1,46 @@
+use super::environment::Environment;
+use super::interpreter::Value;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::fmt;
+use std::rc::Rc;
+
+// An imported file, its members are the top level bindings of the file.
+pub struct Module {
+    pub name: String,
+    env: Rc<RefCell<Environment>>,
+}
+
+impl Module {
+    pub fn new(name: String, env: Rc<RefCell<Environment>>) -> Self {
+        Self { name, env }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.env.borrow().get_at(0, name)
+    }
+}
+
+impl PartialEq for Module {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl PartialOrd for Module {
+    fn partial_cmp(&self, _other: &Self) -> Option<Ordering> {
+        None
+    }
+}
+
+impl fmt::Debug for Module {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Module({})", self.name)
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<module {}>", self.name)
+    }
+}
@@ -0,0 +1,83 @@
+use super::interpreter::Value;
+use std::cmp::Ordering;
+use std::fmt;
+
+// Errors are plain messages, the interpreter attaches them to the call site.
+// Callbacks are closures so natives can carry their own state.
+pub type NativeFn = Box<dyn Fn(&[Value]) -> Result<Value, String>>;
+
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: usize,
+    pub function: NativeFn,
+}
+
+impl NativeFunction {
+    pub fn new<F>(name: &str, arity: usize, function: F) -> Self
+    where
+        F: Fn(&[Value]) -> Result<Value, String> + 'static,
+    {
+        Self {
+            name: name.into(),
+            arity,
+            function: Box::new(function),
+        }
+    }
+
+    pub fn call(&self, arguments: &[Value]) -> Result<Value, String> {
+        (self.function)(arguments)
+    }
+}
+
+// Argument helpers for natives, `index` is only used to name the argument in
+// the error.
+pub fn string_argument(arguments: &[Value], index: usize) -> Result<&str, String> {
+    match &arguments[index] {
+        Value::String(string) => Ok(string),
+        _ => Err(expected("a string", index)),
+    }
+}
+
+pub fn integer_argument(arguments: &[Value], index: usize) -> Result<i64, String> {
+    arguments[index]
+        .integral()
+        .ok_or_else(|| expected("an integer", index))
+}
+
+pub fn number_argument(arguments: &[Value], index: usize) -> Result<f64, String> {
+    arguments[index]
+        .as_f64()
+        .ok_or_else(|| expected("a number", index))
+}
+
+pub fn expected(what: &str, index: usize) -> String {
+    let ordinal = ["first", "second", "third", "fourth"]
+        .get(index)
+        .copied()
+        .unwrap_or("last");
+    format!("Expected {} as the {} argument.", what, ordinal)
+}
+
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl PartialOrd for NativeFunction {
+    fn partial_cmp(&self, _other: &Self) -> Option<Ordering> {
+        None
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NativeFunction({})", self.name)
+    }
+}
+
+impl fmt::Display for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
@@ -0,0 +1,145 @@
+use super::interpreter::Value;
+use super::native::{expected, NativeFunction};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub fn natives() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction::new("clock", 0, clock),
+        NativeFunction::new("type", 1, type_of),
+        NativeFunction::new("className", 1, class_name),
+        NativeFunction::new("str", 1, str),
+        NativeFunction::new("num", 1, num),
+        NativeFunction::new("bool", 1, bool),
+    ]
+}
+
+// Seconds since the epoch, only meant for measuring elapsed time.
+fn clock(_arguments: &[Value]) -> Result<Value, String> {
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|error| error.to_string())?;
+    Ok(Value::F64(elapsed.as_secs_f64()))
+}
+
+fn type_of(arguments: &[Value]) -> Result<Value, String> {
+    let name = match &arguments[0] {
+        Value::String(_) => "string",
+        Value::Int(_) => "int",
+        Value::F64(_) => "float",
+        Value::Boolean(_) => "boolean",
+        Value::Function(_) | Value::NativeFunction(_) => "function",
+        Value::Class(_) => "class",
+        Value::Instance(_) => "instance",
+        Value::List(_) => "list",
+        Value::Map(_) => "map",
+        Value::Module(_) => "module",
+        Value::Nil => "nil",
+    };
+    Ok(Value::String(name.into()))
+}
+
+// The name of a class, or of the class an instance was made from.
+fn class_name(arguments: &[Value]) -> Result<Value, String> {
+    match &arguments[0] {
+        Value::Class(class) => Ok(Value::String(class.name.clone())),
+        Value::Instance(instance) => Ok(Value::String(instance.borrow().class.name.clone())),
+        _ => Err(expected("a class or an instance", 0)),
+    }
+}
+
+fn str(arguments: &[Value]) -> Result<Value, String> {
+    Ok(Value::String(arguments[0].to_string()))
+}
+
+// Numbers are returned as they are, anything that isn't a number or a
+// string holding one gives nil.
+fn num(arguments: &[Value]) -> Result<Value, String> {
+    let string = match &arguments[0] {
+        Value::Int(_) | Value::F64(_) => return Ok(arguments[0].clone()),
+        Value::String(string) => string.trim(),
+        _ => return Ok(Value::Nil),
+    };
+    if let Ok(int) = string.parse() {
+        return Ok(Value::Int(int));
+    }
+    match string.parse::<f64>() {
+        Ok(number) if number.is_finite() => Ok(Value::F64(number)),
+        _ => Ok(Value::Nil),
+    }
+}
+
+fn bool(arguments: &[Value]) -> Result<Value, String> {
+    Ok(Value::Boolean(arguments[0].truthyness()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lox::interpreter::interpreter::tests::{interpret, runtime_error, variable};
+
+    #[test]
+    fn clock_is_in_the_prelude() {
+        let interpreter = interpret("var start = clock(); var elapsed = clock() - start;");
+        match variable(&interpreter, "elapsed") {
+            Value::F64(elapsed) => assert!(elapsed >= 0.0),
+            value => panic!("Expected a number, got {:?}", value),
+        }
+        assert_eq!(
+            runtime_error("clock(1);").message,
+            "Expected 0 arguments but got 1."
+        );
+    }
+
+    #[test]
+    fn type_and_conversion_natives() {
+        let source = r#"
+            class Point {}
+            fun f() {}
+            var types = [
+              type("a"), type(1), type(1.5), type(true), type(nil), type(f),
+              type(clock), type(Point), type(Point()), type([]), type({})
+            ];
+            var names = [className(Point), className(Point())];
+            var strings = [str(1.5), str([1, "a"]), str(true)];
+            var numbers = [num("3.5"), num(" 42 "), num(7), num("abc"), num("inf"), num(true)];
+            var bools = [bool(0), bool(""), bool(nil), bool(false)];
+        "#;
+        let interpreter = interpret(source);
+        assert_eq!(
+            variable(&interpreter, "types").to_string(),
+            "[string, int, float, boolean, nil, function, function, class, instance, list, map]"
+        );
+        assert_eq!(
+            variable(&interpreter, "names").to_string(),
+            "[Point, Point]"
+        );
+        assert_eq!(
+            variable(&interpreter, "strings").to_string(),
+            "[1.5, [1, a], true]"
+        );
+        match variable(&interpreter, "numbers") {
+            Value::List(numbers) => assert_eq!(
+                numbers.borrow().elements,
+                vec![
+                    Value::F64(3.5),
+                    Value::Int(42),
+                    Value::Int(7),
+                    Value::Nil,
+                    Value::Nil,
+                    Value::Nil
+                ]
+            ),
+            value => panic!("Expected a list, got {:?}", value),
+        }
+        assert_eq!(
+            variable(&interpreter, "bools").to_string(),
+            "[true, true, false, false]"
+        );
+
+        let error = runtime_error("className(1);");
+        assert_eq!(
+            error.message,
+            "Expected a class or an instance as the first argument."
+        );
+    }
+}
@@ -0,0 +1,194 @@
+use super::interpreter::Value;
+use super::list::new_list;
+use super::native::{expected, integer_argument, string_argument, NativeFunction};
+
+// Positions and lengths are counted in chars, not bytes.
+pub fn natives() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction::new("len", 1, len),
+        NativeFunction::new("substr", 3, substr),
+        NativeFunction::new("indexOf", 2, index_of),
+        NativeFunction::new("split", 2, split),
+        NativeFunction::new("join", 2, join),
+        NativeFunction::new("trim", 1, trim),
+        NativeFunction::new("upper", 1, upper),
+        NativeFunction::new("lower", 1, lower),
+        NativeFunction::new("replace", 3, replace),
+        NativeFunction::new("startsWith", 2, starts_with),
+    ]
+}
+
+fn len(arguments: &[Value]) -> Result<Value, String> {
+    let len = match &arguments[0] {
+        Value::String(string) => string.chars().count(),
+        Value::List(list) => list.borrow().elements.len(),
+        Value::Map(map) => map.borrow().entries.len(),
+        _ => return Err(expected("a string, list or map", 0)),
+    };
+    Ok(Value::Int(len as i64))
+}
+
+// The characters from `start` up to, but not including, `end`.
+fn substr(arguments: &[Value]) -> Result<Value, String> {
+    let string = string_argument(arguments, 0)?;
+    let start = integer_argument(arguments, 1)?;
+    let end = integer_argument(arguments, 2)?;
+    let len = string.chars().count() as i64;
+    if start < 0 || start > end || end > len {
+        return Err("Substring range out of bounds.".into());
+    }
+    let substring = string
+        .chars()
+        .skip(start as usize)
+        .take((end - start) as usize)
+        .collect();
+    Ok(Value::String(substring))
+}
+
+// -1 when the string doesn't contain the needle.
+fn index_of(arguments: &[Value]) -> Result<Value, String> {
+    let string = string_argument(arguments, 0)?;
+    let needle = string_argument(arguments, 1)?;
+    let index = match string.find(needle) {
+        Some(byte) => string[..byte].chars().count() as i64,
+        None => -1,
+    };
+    Ok(Value::Int(index))
+}
+
+// An empty separator splits the string into its characters.
+fn split(arguments: &[Value]) -> Result<Value, String> {
+    let string = string_argument(arguments, 0)?;
+    let separator = string_argument(arguments, 1)?;
+    let parts = if separator.is_empty() {
+        string
+            .chars()
+            .map(|c| Value::String(c.to_string()))
+            .collect()
+    } else {
+        string
+            .split(separator)
+            .map(|part| Value::String(part.into()))
+            .collect()
+    };
+    Ok(new_list(parts))
+}
+
+fn join(arguments: &[Value]) -> Result<Value, String> {
+    let list = match &arguments[0] {
+        Value::List(list) => list,
+        _ => return Err(expected("a list", 0)),
+    };
+    let separator = string_argument(arguments, 1)?;
+    let parts: Vec<String> = list
+        .borrow()
+        .elements
+        .iter()
+        .map(Value::to_string)
+        .collect();
+    Ok(Value::String(parts.join(separator)))
+}
+
+fn trim(arguments: &[Value]) -> Result<Value, String> {
+    let string = string_argument(arguments, 0)?;
+    Ok(Value::String(string.trim().into()))
+}
+
+fn upper(arguments: &[Value]) -> Result<Value, String> {
+    let string = string_argument(arguments, 0)?;
+    Ok(Value::String(string.to_uppercase()))
+}
+
+fn lower(arguments: &[Value]) -> Result<Value, String> {
+    let string = string_argument(arguments, 0)?;
+    Ok(Value::String(string.to_lowercase()))
+}
+
+// Replaces every occurrence.
+fn replace(arguments: &[Value]) -> Result<Value, String> {
+    let string = string_argument(arguments, 0)?;
+    let from = string_argument(arguments, 1)?;
+    let to = string_argument(arguments, 2)?;
+    if from.is_empty() {
+        return Err("Can't replace an empty string.".into());
+    }
+    Ok(Value::String(string.replace(from, to)))
+}
+
+fn starts_with(arguments: &[Value]) -> Result<Value, String> {
+    let string = string_argument(arguments, 0)?;
+    let prefix = string_argument(arguments, 1)?;
+    Ok(Value::Boolean(string.starts_with(prefix)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lox::interpreter::interpreter::tests::{interpret, runtime_error, variable};
+
+    #[test]
+    fn string_natives() {
+        let source = r#"
+            var s = "héllo wörld";
+            var length = len(s);
+            var sizes = [len([1, 2]), len({"a": 1})];
+            var sub = substr(s, 1, 5);
+            var found = indexOf(s, "wö");
+            var missing = indexOf(s, "x");
+            var words = split("a,b,,c", ",");
+            var chars = split("añb", "");
+            var joined = join([1, "b", true], "-");
+            var trimmed = trim("  a b  ");
+            var shout = upper(s);
+            var quiet = lower("ÀB");
+            var replaced = replace("a-b-c", "-", "+");
+            var starts = startsWith(s, "hé");
+        "#;
+        let interpreter = interpret(source);
+        let get = |name: &str| variable(&interpreter, name).to_string();
+        assert_eq!(variable(&interpreter, "length"), Value::Int(11));
+        assert_eq!(get("sizes"), "[2, 1]");
+        assert_eq!(get("sub"), "éllo");
+        assert_eq!(variable(&interpreter, "found"), Value::Int(6));
+        assert_eq!(variable(&interpreter, "missing"), Value::Int(-1));
+        assert_eq!(get("words"), "[a, b, , c]");
+        assert_eq!(get("chars"), "[a, ñ, b]");
+        assert_eq!(get("joined"), "1-b-true");
+        assert_eq!(get("trimmed"), "a b");
+        assert_eq!(get("shout"), "HÉLLO WÖRLD");
+        assert_eq!(get("quiet"), "àb");
+        assert_eq!(get("replaced"), "a+b+c");
+        assert_eq!(variable(&interpreter, "starts"), Value::Boolean(true));
+    }
+
+    #[test]
+    fn bad_string_native_arguments() {
+        for (source, message) in &[
+            (
+                "len(1);",
+                "Expected a string, list or map as the first argument.",
+            ),
+            ("upper(1);", "Expected a string as the first argument."),
+            (
+                "indexOf(\"a\", nil);",
+                "Expected a string as the second argument.",
+            ),
+            (
+                "substr(\"abc\", 0, 1.5);",
+                "Expected an integer as the third argument.",
+            ),
+            ("substr(\"abc\", 2, 4);", "Substring range out of bounds."),
+            ("substr(\"abc\", 2, 1);", "Substring range out of bounds."),
+            (
+                "join(\"abc\", \"\");",
+                "Expected a list as the first argument.",
+            ),
+            (
+                "replace(\"abc\", \"\", \"x\");",
+                "Can't replace an empty string.",
+            ),
+        ] {
+            assert_eq!(runtime_error(source).message, *message);
+        }
+    }
+}
@@ -1,10 +1,14 @@
+pub mod diagnostic;
 pub mod expr;
 pub mod interpreter;
 pub mod parser;
+pub mod resolver;
 pub mod scanner;
 pub mod stmt;
+pub mod testing;
 pub mod token;
 use std::fs;
+use std::path::PathBuf;
 use std::process;
 extern crate derive_more;
 extern crate phf;
@@ -18,6 +22,8 @@ use rustyline::Editor;
 pub struct Lox {
     had_errors: bool,
     had_runtime_errors: bool,
+    seed: Option<u64>,
+    sandbox: Option<PathBuf>,
 }
 
 impl Lox {
@@ -25,19 +31,39 @@ impl Lox {
         Lox {
             had_errors: false,
             had_runtime_errors: false,
+            seed: None,
+            sandbox: None,
         }
     }
 
-    pub fn prompt(&mut self) {
+    pub fn seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+    }
+
+    pub fn sandbox(&mut self, root: PathBuf) {
+        self.sandbox = Some(root);
+    }
+
+    fn interpreter(&self) -> Interpreter {
         let mut interpreter = Interpreter::new();
+        if let Some(seed) = self.seed {
+            interpreter.seed(seed);
+        }
+        if let Some(root) = &self.sandbox {
+            interpreter.sandbox(root.clone());
+        }
+        interpreter
+    }
+
+    pub fn prompt(&mut self) {
+        let mut interpreter = self.interpreter();
         let mut rl = Editor::<()>::new();
         loop {
             let readline = rl.readline(">> ");
             match readline {
                 Ok(line) => {
                     rl.add_history_entry(line.as_str());
-                    self.run(&mut interpreter, line);
-                    self.had_errors = false;
+                    self.run_line(&mut interpreter, line);
                 }
                 Err(ReadlineError::Interrupted) => {
                     println!("CTRL-C");
@@ -55,22 +81,133 @@ impl Lox {
         }
     }
 
-    pub fn runfile(&self, path: std::path::PathBuf) {
-        let source = fs::read_to_string(path).unwrap_or_else(|_| "".to_string());
-        let mut interpreter = Interpreter::new();
+    // Errors on one line of the REPL are reported and then forgotten, the
+    // interpreter keeps its state for the next line.
+    fn run_line(&mut self, interpreter: &mut Interpreter, line: String) {
+        self.run(interpreter, line);
+        self.had_errors = false;
+        self.had_runtime_errors = false;
+    }
+
+    pub fn runfile(&mut self, path: std::path::PathBuf) {
+        let source = fs::read_to_string(&path).unwrap_or_else(|_| "".to_string());
+        let mut interpreter = self.interpreter();
+        interpreter.set_script(&path);
         self.run(&mut interpreter, source);
         if self.had_errors {
             process::exit(1);
         }
+        if self.had_runtime_errors {
+            process::exit(70);
+        }
     }
 
-    fn run(&self, interpreter: &mut Interpreter, source: String) {
+    // Runs every test file under `root` in a fresh interpreter.
+    pub fn test(&mut self, root: PathBuf) {
+        let tests = testing::find_tests(&root).unwrap_or_else(|error| {
+            eprintln!("Can't read '{}': {}", root.display(), error);
+            process::exit(1);
+        });
+        let mut report = testing::Report::default();
+        for test in &tests {
+            testing::run_test(self.interpreter(), test, &mut report);
+        }
+
+        for failure in &report.failures {
+            println!("FAIL {}", failure);
+        }
+        println!(
+            "{} files, {} passed, {} failed ({} assertions held)",
+            tests.len(),
+            report.passed,
+            report.failed,
+            report.assertions
+        );
+        if report.failed > 0 {
+            process::exit(1);
+        }
+    }
+
+    fn run(&mut self, interpreter: &mut Interpreter, source: String) {
         let mut scanner = scanner::Scanner::new(&source);
-        let tokens = scanner.scan_text();
-        let mut parser = parser::Parser::new(tokens);
-        let statements = parser.parse();
-        for node in statements {
-            interpreter.evaluate_node(node);
+        let (tokens, errors) = scanner.scan_text();
+        if !errors.is_empty() {
+            for error in errors {
+                eprintln!("{}", error);
+                if let Some(snippet) = diagnostic::render(&source, error.span) {
+                    eprint!("{}", snippet);
+                }
+            }
+            self.had_errors = true;
+            return;
+        }
+        let mut parser = parser::Parser::new(&tokens);
+        let (mut statements, errors) = parser.parse();
+        if !errors.is_empty() {
+            for error in errors {
+                eprintln!("{}", error);
+                if let Some(snippet) = diagnostic::render(&source, error.span) {
+                    eprint!("{}", snippet);
+                }
+            }
+            self.had_errors = true;
+            return;
+        }
+        let mut resolver = resolver::Resolver::new();
+        if let Err(errors) = resolver.resolve(&mut statements) {
+            for error in errors {
+                eprintln!("{}", error);
+                if let Some(snippet) = diagnostic::render(&source, error.span) {
+                    eprint!("{}", snippet);
+                }
+            }
+            self.had_errors = true;
+            return;
+        }
+        for node in &statements {
+            if let Err(error) = interpreter.evaluate_node(node) {
+                eprintln!("{}", error);
+                // Errors from imported files are drawn against that file.
+                let snippet = match &error.file {
+                    Some(file) => fs::read_to_string(file)
+                        .ok()
+                        .and_then(|module| diagnostic::render(&module, error.span)),
+                    None => diagnostic::render(&source, error.span),
+                };
+                if let Some(snippet) = snippet {
+                    eprint!("{}", snippet);
+                }
+                self.had_runtime_errors = true;
+                return;
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lox::interpreter::interpreter::STACK_SIZE;
+
+    #[test]
+    fn the_repl_survives_runaway_recursion() {
+        let run = || {
+            let mut lox = Lox::new();
+            let mut interpreter = lox.interpreter();
+            lox.run_line(&mut interpreter, "fun f(n) { return f(n + 1); }".into());
+            lox.run_line(
+                &mut interpreter,
+                "f(0); assert(true, \"unreachable\");".into(),
+            );
+            lox.run_line(&mut interpreter, "assertEqual(str(f), \"<fn f>\");".into());
+            interpreter.assertions_passed()
+        };
+        let passed = std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(run)
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(passed, 1);
+    }
+}
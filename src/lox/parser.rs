@@ -1,129 +1,198 @@
-use super::expr::{Assignment, Binary, Expr, Grouping, Literal, Logical, Unary, Var};
-use super::stmt::{self, Block, IfStmt, Stmt, While};
-use super::token::{self, Token, TokenType};
+use super::expr::{
+    Assignment, Binary, Call, Expr, Get, Grouping, Index, List, Literal, Logical, Map, Set,
+    SetIndex, Stringify, Super, Ternary, This, Unary, Update, Var,
+};
+use super::stmt::{
+    self, Block, Break, Catch, Class, Continue, Defer, Function, IfStmt, Import, Return, Stmt,
+    Throw, Try, While,
+};
+use super::token::{self, Span, Token, TokenType};
+use std::fmt;
 use std::iter::Peekable;
+use std::rc::Rc;
 use std::slice::Iter;
 
+#[derive(PartialEq, Debug, Clone)]
+pub struct ParseError {
+    pub token: Box<Token>,
+    pub message: String,
+    pub span: Span,
+}
+
+impl ParseError {
+    pub fn new(token: Token, message: &str) -> Self {
+        let span = token.span;
+        ParseError {
+            token: Box::new(token),
+            message: message.into(),
+            span,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.token.t_type == TokenType::EOF {
+            write!(
+                f,
+                "[line {}] Error at end: {}",
+                self.token.line, self.message
+            )
+        } else {
+            write!(
+                f,
+                "[line {}] Error at '{}': {}",
+                self.token.line, self.token.lexeme, self.message
+            )
+        }
+    }
+}
+
+type ParseResult<T> = Result<T, ParseError>;
+
 #[derive(Debug)]
 pub struct Parser<'a> {
     token_list: Peekable<Iter<'a, Token>>,
-    error: bool,
+    last_line: u32,
+    loop_depth: usize,
+    block_depth: usize,
+    errors: Vec<ParseError>,
 }
 
 impl<'a> Parser<'a> {
     pub fn new(borrowed_token_list: &'a [Token]) -> Self {
         Parser {
             token_list: borrowed_token_list.iter().peekable(),
-            error: false,
+            last_line: 1,
+            loop_depth: 0,
+            block_depth: 0,
+            errors: Vec::new(),
         }
     }
 
-    pub fn parse(&mut self) -> Vec<Stmt> {
+    pub fn parse(&mut self) -> (Vec<Stmt>, Vec<ParseError>) {
         let mut statements = Vec::new();
-        while let Some(parsed_expression) = self.next_stmt() {
-            statements.push(parsed_expression);
+        while !self.is_at_end() {
+            match self.next_stmt() {
+                Ok(stmt) => statements.push(stmt),
+                Err(error) => {
+                    self.errors.push(error);
+                    self.synchronize();
+                }
+            }
         }
 
-        statements
+        (statements, std::mem::take(&mut self.errors))
     }
 
-    fn next_stmt(&mut self) -> Option<Stmt> {
-        let peek_token = self.token_list.peek()?;
+    // Skips tokens until what looks like the start of the next statement, or
+    // the end of the enclosing block, so a single mistake doesn't cascade into
+    // errors for the rest of the file. Braces opened while skipping are
+    // skipped along with everything inside them.
+    fn synchronize(&mut self) {
+        let mut depth = 0;
+        while !self.is_at_end() {
+            if let Some(token) = self.advance() {
+                match token.t_type {
+                    TokenType::LeftBrace => depth += 1,
+                    TokenType::RightBrace if depth > 0 => depth -= 1,
+                    TokenType::Semicolon if depth == 0 => return,
+                    _ => (),
+                }
+            }
+            if depth > 0 {
+                continue;
+            }
 
-        match peek_token.t_type {
+            match self.peek_type() {
+                TokenType::Class
+                | TokenType::Fun
+                | TokenType::Var
+                | TokenType::For
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Print
+                | TokenType::Return
+                | TokenType::Break
+                | TokenType::Continue
+                | TokenType::Defer
+                | TokenType::Import
+                | TokenType::Throw
+                | TokenType::Try => return,
+                TokenType::RightBrace if self.block_depth > 0 => return,
+                _ => (),
+            }
+        }
+    }
+
+    fn next_stmt(&mut self) -> ParseResult<Stmt> {
+        match self.peek_type() {
             TokenType::Print => {
-                self.token_list.next();
+                self.advance();
                 self.print_statement()
             }
             TokenType::Var => {
-                self.token_list.next();
+                self.advance();
                 self.variable_declaration()
             }
             TokenType::LeftBrace => {
-                self.token_list.next();
+                self.advance();
                 self.block_statement()
             }
             TokenType::For => {
-                self.token_list.next();
+                self.advance();
                 self.for_loop()
             }
             TokenType::If => {
-                self.token_list.next();
+                self.advance();
                 self.if_statement()
             }
             TokenType::While => {
-                self.token_list.next();
+                self.advance();
                 self.while_statement()
             }
+            TokenType::Fun => {
+                self.advance();
+                self.function_declaration()
+            }
+            TokenType::Class => {
+                self.advance();
+                self.class_declaration()
+            }
+            TokenType::Return => self.return_statement(),
+            TokenType::Break => self.break_statement(),
+            TokenType::Continue => self.continue_statement(),
+            TokenType::Defer => self.defer_statement(),
+            TokenType::Throw => self.throw_statement(),
+            TokenType::Import => self.import_statement(),
+            TokenType::Try => self.try_statement(),
             _ => self.stmt_expr(),
         }
     }
 
-    fn print_statement(&mut self) -> Option<Stmt> {
-        let value = self.expression();
-        let next_token = self.token_list.peek();
-        if next_token == None || value == None {
-            return None;
-        }
-
-        if next_token?.t_type != TokenType::Semicolon {
-            println!("Expect ; after value")
-        }
-        self.token_list.next();
+    fn print_statement(&mut self) -> ParseResult<Stmt> {
+        let value = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
 
-        Some(Stmt::Print(value.unwrap()))
+        Ok(Stmt::Print(value))
     }
 
-    fn variable_declaration(&mut self) -> Option<Stmt> {
-        let name = self.token_list.next()?;
-
-        let next_token = self.token_list.peek();
-        if next_token == None {
-            println!("Expect ; after value");
-            return None;
-        }
-        let next_token = next_token.unwrap();
+    fn variable_declaration(&mut self) -> ParseResult<Stmt> {
+        let name = self.consume(TokenType::Identifier, "Expect variable name.")?;
 
-        if next_token.t_type == TokenType::Semicolon {
-            return Some(self.empty_init(name));
-        }
-
-        if next_token.t_type != TokenType::Equal {
-            println!("Expected = or ; after var");
-            return None;
+        if self.check(TokenType::Semicolon) {
+            self.advance();
+            return Ok(self.empty_init(&name));
         }
 
-        let equal_token = self.token_list.next();
-
-        let expr_value = self.expression();
-
-        let value = if expr_value == None {
-            let token = Token::new(
-                TokenType::Nil,
-                "".to_owned(),
-                token::Literal::None,
-                equal_token?.line,
-            );
-            let literal = Literal { token };
-
-            Expr::Literal(literal)
-        } else {
-            expr_value.unwrap()
-        };
-
-        let variable = stmt::Var {
-            value,
-            name: name.lexeme.to_owned(),
-        };
+        self.consume(TokenType::Equal, "Expect '=' or ';' after variable name.")?;
+        let value = self.expression()?;
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after variable declaration.",
+        )?;
 
-        let next_token = self.token_list.next();
-
-        if let Some(token) = next_token {
-            if token.t_type == TokenType::Semicolon {
-                return Some(Stmt::Declaration(variable));
-            }
-        }
-        panic!("Expect ; after declaration");
+        Ok(Stmt::Declaration(stmt::Var { value, name }))
     }
 
     fn empty_init(&mut self, name: &Token) -> Stmt {
@@ -132,408 +201,900 @@ impl<'a> Parser<'a> {
             "".to_owned(),
             token::Literal::None,
             name.line,
-        );
+        )
+        .with_span(name.span);
         let literal = Literal { token };
         let value = Expr::Literal(literal);
 
         let variable = stmt::Var {
             value,
-            name: name.lexeme.to_owned(),
+            name: name.clone(),
         };
 
         Stmt::Declaration(variable)
     }
 
-    fn block_statement(&mut self) -> Option<Stmt> {
-        let mut statements = vec![];
+    fn class_declaration(&mut self) -> ParseResult<Stmt> {
+        let name = self.consume(TokenType::Identifier, "Expect class name.")?;
+
+        let superclass = if self.check(TokenType::Less) {
+            self.advance();
+            let superclass_name = self.consume(TokenType::Identifier, "Expect superclass name.")?;
+            Some(Var {
+                name: superclass_name,
+                depth: None,
+            })
+        } else {
+            None
+        };
+
+        self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?;
+
+        let mut methods = vec![];
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            methods.push(Rc::new(self.function()?));
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after class body.")?;
+
+        Ok(Stmt::Class(Class {
+            name,
+            superclass,
+            methods,
+        }))
+    }
 
-        while let Some(next_token) = self.token_list.peek() {
-            if next_token.t_type == TokenType::RightBrace {
-                break;
+    fn function_declaration(&mut self) -> ParseResult<Stmt> {
+        self.function()
+            .map(|function| Stmt::Function(Rc::new(function)))
+    }
+
+    fn function(&mut self) -> ParseResult<Function> {
+        let name = self.consume(TokenType::Identifier, "Expect function name.")?;
+        self.consume(TokenType::LeftParen, "Expect '(' after function name.")?;
+
+        let mut params = vec![];
+        if !self.check(TokenType::RightParen) {
+            loop {
+                params.push(self.consume(TokenType::Identifier, "Expect parameter name.")?);
+                if !self.check(TokenType::Comma) {
+                    break;
+                }
+                self.advance();
             }
+        }
+
+        self.consume(TokenType::RightParen, "Expect ')' after parameters.")?;
+        self.consume(TokenType::LeftBrace, "Expect '{' before function body.")?;
+
+        // A loop around the declaration doesn't make `break` valid inside it.
+        let enclosing_loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        let body = self.block();
+        self.loop_depth = enclosing_loop_depth;
+        Ok(Function {
+            name,
+            params,
+            body: body?,
+        })
+    }
+
+    fn return_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword = self.consume(TokenType::Return, "Expect 'return'.")?;
 
-            let next_stmt = self.next_stmt()?;
-            statements.push(next_stmt);
+        let value = if self.check(TokenType::Semicolon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenType::Semicolon, "Expect ';' after return value.")?;
+
+        Ok(Stmt::Return(Return { keyword, value }))
+    }
+
+    fn break_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword = self.consume(TokenType::Break, "Expect 'break'.")?;
+        // The statement itself is well formed, so report it without
+        // throwing the parser into panic mode.
+        if self.loop_depth == 0 {
+            let error = ParseError::new(keyword.clone(), "Can't use 'break' outside of a loop.");
+            self.errors.push(error);
         }
+        self.consume(TokenType::Semicolon, "Expect ';' after 'break'.")?;
+
+        Ok(Stmt::Break(Break { keyword }))
+    }
 
-        if self.token_list.peek() == None {
-            panic!("Missing closing bracket");
+    fn continue_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword = self.consume(TokenType::Continue, "Expect 'continue'.")?;
+        if self.loop_depth == 0 {
+            let error = ParseError::new(keyword.clone(), "Can't use 'continue' outside of a loop.");
+            self.errors.push(error);
         }
+        self.consume(TokenType::Semicolon, "Expect ';' after 'continue'.")?;
+
+        Ok(Stmt::Continue(Continue { keyword }))
+    }
+
+    fn defer_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword = self.consume(TokenType::Defer, "Expect 'defer'.")?;
+        let body = Box::new(self.next_stmt()?);
 
-        if self.token_list.peek()?.t_type == TokenType::RightBrace {
-            self.token_list.next();
+        Ok(Stmt::Defer(Defer { keyword, body }))
+    }
+
+    fn import_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword = self.consume(TokenType::Import, "Expect 'import'.")?;
+        let path = self.consume(TokenType::String, "Expect module path after 'import'.")?;
+
+        // `as` is only special here, so it isn't a keyword.
+        let name = if self.peek_lexeme() == Some("as") {
+            self.advance();
+            self.consume(TokenType::Identifier, "Expect module name after 'as'.")?
+        } else {
+            match Parser::module_name(&path) {
+                Some(name) => name,
+                None => return Err(self.error("Expect 'as' and a name for this module.")),
+            }
+        };
+        self.consume(TokenType::Semicolon, "Expect ';' after import.")?;
+
+        Ok(Stmt::Import(Import {
+            keyword,
+            path,
+            name,
+        }))
+    }
+
+    // The file stem of the path, if it can be used as a variable name.
+    fn module_name(path: &Token) -> Option<Token> {
+        let stem = match &path.literal {
+            token::Literal::String(path) => std::path::Path::new(path).file_stem()?.to_str()?,
+            _ => return None,
+        };
+        let mut chars = stem.chars();
+        let starts_like_identifier = chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+        if !starts_like_identifier || !chars.all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return None;
         }
+        let name = Token::new(
+            TokenType::Identifier,
+            stem.into(),
+            token::Literal::None,
+            path.line,
+        );
+        Some(name.with_span(path.span))
+    }
 
-        Some(Stmt::Block(Block {
+    fn throw_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword = self.consume(TokenType::Throw, "Expect 'throw'.")?;
+        let value = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after thrown value.")?;
+
+        Ok(Stmt::Throw(Throw { keyword, value }))
+    }
+
+    fn try_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword = self.consume(TokenType::Try, "Expect 'try'.")?;
+        self.consume(TokenType::LeftBrace, "Expect '{' after 'try'.")?;
+        let body = self.block()?;
+
+        let catch = if self.check(TokenType::Catch) {
+            self.advance();
+            self.consume(TokenType::LeftParen, "Expect '(' after 'catch'.")?;
+            let name = self.consume(TokenType::Identifier, "Expect exception variable name.")?;
+            self.consume(
+                TokenType::RightParen,
+                "Expect ')' after exception variable.",
+            )?;
+            self.consume(TokenType::LeftBrace, "Expect '{' before catch body.")?;
+            Some(Catch {
+                name,
+                body: self.block()?,
+            })
+        } else {
+            None
+        };
+
+        let finally = if self.check(TokenType::Finally) {
+            self.advance();
+            self.consume(TokenType::LeftBrace, "Expect '{' after 'finally'.")?;
+            Some(self.block()?)
+        } else {
+            None
+        };
+
+        if catch.is_none() && finally.is_none() {
+            return Err(self.error("Expect 'catch' or 'finally' after try block."));
+        }
+
+        Ok(Stmt::Try(Try {
+            keyword,
+            body,
+            catch,
+            finally,
+        }))
+    }
+
+    fn loop_body(&mut self) -> ParseResult<Stmt> {
+        self.loop_depth += 1;
+        let body = self.next_stmt();
+        self.loop_depth -= 1;
+        body
+    }
+
+    fn block_statement(&mut self) -> ParseResult<Stmt> {
+        let statements = self.block()?;
+
+        Ok(Stmt::Block(Block {
             stmt_vec: statements,
         }))
     }
 
-    fn for_loop(&mut self) -> Option<Stmt> {
-        let next = self.token_list.next()?;
-        if next.t_type != TokenType::LeftParen {
-            panic!("Expect `(` after `for`.");
+    fn block(&mut self) -> ParseResult<Vec<Stmt>> {
+        let mut statements = vec![];
+
+        self.block_depth += 1;
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            match self.next_stmt() {
+                Ok(stmt) => statements.push(stmt),
+                // Recover inside the block so the rest of it isn't parsed as
+                // top level code. An error at the closing brace leaves it for
+                // the block to consume.
+                Err(error) => {
+                    self.errors.push(error);
+                    if !self.check(TokenType::RightBrace) {
+                        self.synchronize();
+                    }
+                }
+            }
         }
+        self.block_depth -= 1;
+        self.consume(TokenType::RightBrace, "Expect '}' after block.")?;
 
-        let peek = self.token_list.peek()?;
-        let initializer = match peek.t_type {
-            TokenType::Semicolon => None,
+        Ok(statements)
+    }
+
+    fn for_loop(&mut self) -> ParseResult<Stmt> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
+
+        let initializer = match self.peek_type() {
+            TokenType::Semicolon => {
+                self.advance();
+                None
+            }
             TokenType::Var => {
-                self.token_list.next();
-                self.variable_declaration()
+                self.advance();
+                Some(self.variable_declaration()?)
             }
-            _ => self.stmt_expr(),
+            _ => Some(self.stmt_expr()?),
         };
-        let peek = self.token_list.peek();
-
-        let written_condition = match peek {
-            None => None,
-            Some(token) => match token.t_type {
-                TokenType::Semicolon => None,
-                _ => self.expression(),
-            },
-        };
-        let next = self.token_list.next()?;
-        if next.t_type != TokenType::Semicolon {
-            panic!("Expect `;` after loop condition.");
-        }
-
-        let peek = self.token_list.peek();
-        let increment = match peek {
-            None => None,
-            Some(token) => match token.t_type {
-                TokenType::RightParen => None,
-                _ => self.expression(),
-            },
+
+        let written_condition = if self.check(TokenType::Semicolon) {
+            None
+        } else {
+            Some(self.expression()?)
         };
+        let semicolon = self.consume(TokenType::Semicolon, "Expect ';' after loop condition.")?;
 
-        let next = self.token_list.next()?;
-        if next.t_type != TokenType::RightParen {
-            panic!("Expect `)` after `for` clauses.");
-        }
+        let increment = if self.check(TokenType::RightParen) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
 
-        let mut written_body = self.next_stmt();
-        if increment != None {
-            let incr = Stmt::Expr(increment?);
-            let stmt_vec = vec![written_body?, incr];
-            written_body = Some(Stmt::Block(Block { stmt_vec }));
-        }
+        let body = self.loop_body()?;
 
-        let condition = if written_condition == None {
+        let condition = written_condition.unwrap_or_else(|| {
             Expr::Literal(Literal {
                 token: Token {
                     t_type: TokenType::True,
                     lexeme: "true".into(),
                     literal: token::Literal::Boolean(true),
-                    line: next.line,
+                    line: semicolon.line,
+                    span: semicolon.span,
                 },
             })
-        } else {
-            written_condition?
-        };
-
-        let body = Box::new(written_body?);
+        });
 
-        let inner_for = Stmt::While(While { condition, body });
+        let inner_for = Stmt::While(While {
+            condition,
+            body: Box::new(body),
+            increment,
+        });
 
-        let desugared_for = if initializer != None {
-            Stmt::Block(Block {
-                stmt_vec: vec![initializer?, inner_for],
-            })
-        } else {
-            inner_for
+        let desugared_for = match initializer {
+            Some(initializer) => Stmt::Block(Block {
+                stmt_vec: vec![initializer, inner_for],
+            }),
+            None => inner_for,
         };
 
-        Some(desugared_for)
+        Ok(desugared_for)
     }
-    fn if_statement(&mut self) -> Option<Stmt> {
-        let next_token = self.token_list.peek();
-        if next_token?.t_type != TokenType::LeftParen {
-            println!("Expect ( after if")
-        }
-        self.token_list.next();
 
+    fn if_statement(&mut self) -> ParseResult<Stmt> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
         let condition = self.expression()?;
-        let next_token = self.token_list.peek();
-        if next_token?.t_type != TokenType::RightParen {
-            println!("Expect ) after if condition")
-        }
-        self.token_list.next();
+        self.consume(TokenType::RightParen, "Expect ')' after if condition.")?;
 
         let if_stmt = self.next_stmt()?;
-        let mut else_stmt = None;
-        let next_token = self.token_list.peek();
-        if next_token?.t_type == TokenType::Else {
-            self.token_list.next();
-            else_stmt = self.next_stmt();
-        }
-        Some(Stmt::If(IfStmt {
+        let else_stmt = if self.check(TokenType::Else) {
+            self.advance();
+            Some(self.next_stmt()?)
+        } else {
+            None
+        };
+
+        Ok(Stmt::If(IfStmt {
             condition,
             truth_branch: Box::new(if_stmt),
             false_branch: Box::new(else_stmt),
         }))
     }
 
-    fn while_statement(&mut self) -> Option<Stmt> {
-        let next_token = self.token_list.peek();
-        if next_token?.t_type != TokenType::LeftParen {
-            println!("Expect ( after while")
-        }
-        self.token_list.next();
-
+    fn while_statement(&mut self) -> ParseResult<Stmt> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
         let condition = self.expression()?;
-        let next_token = self.token_list.peek();
-        if next_token?.t_type != TokenType::RightParen {
-            println!("Expect ) after while condition")
-        }
-        self.token_list.next();
+        self.consume(TokenType::RightParen, "Expect ')' after while condition.")?;
 
-        if self.token_list.peek()?.t_type != TokenType::LeftBrace {
-            println!("Expected a block after while condition");
-            return None;
+        if !self.check(TokenType::LeftBrace) {
+            return Err(self.error("Expect a block after while condition."));
         }
 
-        let next_stmt = self.next_stmt()?;
-
-        let body = Box::new(next_stmt);
-        let result = Stmt::While(While { condition, body });
-        Some(result)
+        let body = Box::new(self.loop_body()?);
+        Ok(Stmt::While(While {
+            condition,
+            body,
+            increment: None,
+        }))
     }
 
-    fn stmt_expr(&mut self) -> Option<Stmt> {
-        let expr = self.assignment();
-        let next_token = self.token_list.peek()?;
+    fn stmt_expr(&mut self) -> ParseResult<Stmt> {
+        let expr = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after expression.")?;
 
-        if next_token.t_type == TokenType::EOF {
-            return None;
-        }
-
-        if next_token.t_type != TokenType::Semicolon {
-            println!("Expect ; after expression")
-        }
-        self.token_list.next();
-        if expr == None {
-            None
-        } else {
-            Some(Stmt::Expr(expr.unwrap()))
-        }
+        Ok(Stmt::Expr(expr))
     }
 
-    fn expression(&mut self) -> Option<Expr> {
+    fn expression(&mut self) -> ParseResult<Expr> {
         self.assignment()
     }
 
-    fn assignment(&mut self) -> Option<Expr> {
-        let possible_expr = self.logic_or();
+    fn assignment(&mut self) -> ParseResult<Expr> {
+        let expr = self.ternary()?;
 
-        if self.token_list.peek().is_none() {
-            return possible_expr;
+        if let TokenType::PlusEqual
+        | TokenType::MinusEqual
+        | TokenType::StarEqual
+        | TokenType::SlashEqual = self.peek_type()
+        {
+            let operator = self.advance().unwrap();
+            let value = self.assignment()?;
+            return Parser::update(expr, operator, value, false);
         }
 
-        let next_token = self.token_list.peek().unwrap();
-
-        if let TokenType::Equal = next_token.t_type {
-            self.token_list.next();
+        if !self.check(TokenType::Equal) {
+            return Ok(expr);
+        }
 
-            if possible_expr == None {
-                panic!("Invalid assignment");
-            }
+        let equals = self.advance().unwrap();
+        let value = Box::new(self.assignment()?);
+
+        match expr {
+            Expr::Var(var) => Ok(Expr::Assignment(Assignment {
+                name: var.name,
+                value,
+                depth: None,
+            })),
+            Expr::Get(get) => Ok(Expr::Set(Set {
+                object: get.object,
+                name: get.name,
+                value,
+            })),
+            Expr::Index(index) => Ok(Expr::SetIndex(SetIndex {
+                object: index.object,
+                index: index.index,
+                value,
+                bracket: index.bracket,
+            })),
+            _ => Err(ParseError::new(equals, "Invalid assignment target.")),
+        }
+    }
 
-            let value = self.assignment();
-            if value == None {
-                panic!("Invalid value on the right hand side");
-            }
+    fn update(target: Expr, operator: Token, value: Expr, postfix: bool) -> ParseResult<Expr> {
+        match target {
+            Expr::Var(_) | Expr::Get(_) | Expr::Index(_) => Ok(Expr::Update(Update {
+                target: Box::new(target),
+                operator,
+                value: Box::new(value),
+                postfix,
+            })),
+            _ => Err(ParseError::new(operator, "Invalid assignment target.")),
+        }
+    }
 
-            let value = Box::new(value.unwrap());
-            let expr = possible_expr.unwrap();
+    // The implicit 1 that `++` and `--` add or subtract.
+    fn one(operator: &Token) -> Expr {
+        let token = Token::new(
+            TokenType::Number,
+            "1".into(),
+            token::Literal::Int(1),
+            operator.line,
+        )
+        .with_span(operator.span);
+        Expr::Literal(Literal { token })
+    }
 
-            if let Expr::Var(var) = expr {
-                let name = var.name;
-                let assignment = Expr::Assignment(Assignment { name, value });
-                return Some(assignment);
-            }
+    // The middle branch can be any expression, the last one nests to the
+    // right so `a ? b : c ? d : e` is `a ? b : (c ? d : e)`.
+    fn ternary(&mut self) -> ParseResult<Expr> {
+        let condition = self.logic_or()?;
 
-            panic!("Invalid assignment")
-        } else {
-            possible_expr
+        if !self.check(TokenType::Question) {
+            return Ok(condition);
         }
+        self.advance();
+        let then_branch = self.expression()?;
+        self.consume(
+            TokenType::Colon,
+            "Expect ':' after then branch of conditional.",
+        )?;
+        let else_branch = self.ternary()?;
+
+        Ok(Expr::Ternary(Ternary {
+            condition: Box::new(condition),
+            then_branch: Box::new(then_branch),
+            else_branch: Box::new(else_branch),
+        }))
     }
 
-    fn logic_or(&mut self) -> Option<Expr> {
-        let mut expr = self.logic_and();
+    fn logic_or(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.logic_and()?;
 
-        while let TokenType::Or = self.token_list.peek()?.t_type {
-            let operator = self.token_list.next()?.clone();
-            let left = Box::new(expr?);
+        while self.check(TokenType::Or) {
+            let operator = self.advance().unwrap();
             let right = Box::new(self.logic_and()?);
 
-            expr = Some(Expr::Logical(Logical {
-                left,
+            expr = Expr::Logical(Logical {
+                left: Box::new(expr),
                 right,
                 operator,
-            }));
+            });
         }
 
-        expr
+        Ok(expr)
     }
-    fn logic_and(&mut self) -> Option<Expr> {
-        let mut expr = self.equality();
 
-        while let TokenType::And = self.token_list.peek()?.t_type {
-            let operator = self.token_list.next()?.clone();
-            let left = Box::new(expr?);
+    fn logic_and(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.equality()?;
+
+        while self.check(TokenType::And) {
+            let operator = self.advance().unwrap();
             let right = Box::new(self.equality()?);
 
-            expr = Some(Expr::Logical(Logical {
-                left,
+            expr = Expr::Logical(Logical {
+                left: Box::new(expr),
                 right,
                 operator,
-            }));
+            });
         }
 
-        expr
+        Ok(expr)
     }
 
-    fn equality(&mut self) -> Option<Expr> {
-        let mut expr = self.comparison();
-
-        if self.token_list.peek() == None {
-            return expr;
-        }
+    fn equality(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.comparison()?;
 
-        if let TokenType::BangEqual | TokenType::EqualEqual = self.token_list.peek()?.t_type {
-            let operator = self.token_list.next()?.clone();
+        while let TokenType::BangEqual | TokenType::EqualEqual = self.peek_type() {
+            let operator = self.advance().unwrap();
             let right = Box::new(self.comparison()?);
-            let left = Box::new(expr?);
-            expr = Some(Expr::Binary(Binary {
-                left,
+            expr = Expr::Binary(Binary {
+                left: Box::new(expr),
                 right,
                 operator,
-            }));
+            });
         }
 
-        expr
+        Ok(expr)
     }
 
-    fn comparison(&mut self) -> Option<Expr> {
-        let mut expr = self.addition();
-        if self.token_list.peek() == None {
-            return expr;
-        }
+    fn comparison(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.bit_or()?;
 
-        if let TokenType::Greater
+        while let TokenType::Greater
         | TokenType::GreaterEqual
         | TokenType::Less
-        | TokenType::LessEqual = self.token_list.peek()?.t_type
+        | TokenType::LessEqual = self.peek_type()
         {
-            let operator = self.token_list.next()?.clone();
-            let right = Box::new(self.multiplication()?);
-            let left = Box::new(expr?);
-            expr = Some(Expr::Binary(Binary {
-                left,
+            let operator = self.advance().unwrap();
+            let right = Box::new(self.bit_or()?);
+            expr = Expr::Binary(Binary {
+                left: Box::new(expr),
                 right,
                 operator,
-            }));
+            });
         }
 
-        expr
+        Ok(expr)
     }
 
-    fn addition(&mut self) -> Option<Expr> {
-        let mut expr = self.multiplication();
+    // Bitwise operators bind tighter than comparisons, so `a & 1 == 0` means
+    // `(a & 1) == 0`.
+    fn bit_or(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.bit_xor()?;
 
-        if self.token_list.peek() == None {
-            return expr;
+        while self.check(TokenType::Pipe) {
+            let operator = self.advance().unwrap();
+            let right = Box::new(self.bit_xor()?);
+            expr = Expr::Binary(Binary {
+                left: Box::new(expr),
+                right,
+                operator,
+            });
         }
-        while let TokenType::Minus | TokenType::Plus = self.token_list.peek()?.t_type {
-            let operator = self.token_list.next()?.clone();
+
+        Ok(expr)
+    }
+
+    fn bit_xor(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.bit_and()?;
+
+        while self.check(TokenType::Caret) {
+            let operator = self.advance().unwrap();
+            let right = Box::new(self.bit_and()?);
+            expr = Expr::Binary(Binary {
+                left: Box::new(expr),
+                right,
+                operator,
+            });
+        }
+
+        Ok(expr)
+    }
+
+    fn bit_and(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.shift()?;
+
+        while self.check(TokenType::Ampersand) {
+            let operator = self.advance().unwrap();
+            let right = Box::new(self.shift()?);
+            expr = Expr::Binary(Binary {
+                left: Box::new(expr),
+                right,
+                operator,
+            });
+        }
+
+        Ok(expr)
+    }
+
+    fn shift(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.addition()?;
+
+        while let TokenType::LessLess | TokenType::GreaterGreater = self.peek_type() {
+            let operator = self.advance().unwrap();
+            let right = Box::new(self.addition()?);
+            expr = Expr::Binary(Binary {
+                left: Box::new(expr),
+                right,
+                operator,
+            });
+        }
+
+        Ok(expr)
+    }
+
+    fn addition(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.multiplication()?;
+
+        while let TokenType::Minus | TokenType::Plus = self.peek_type() {
+            let operator = self.advance().unwrap();
             let right = Box::new(self.multiplication()?);
-            let left = Box::new(expr?);
-            expr = Some(Expr::Binary(Binary {
-                left,
+            expr = Expr::Binary(Binary {
+                left: Box::new(expr),
                 right,
                 operator,
-            }));
-            if self.token_list.peek() == None {
-                break;
-            }
+            });
         }
 
-        expr
+        Ok(expr)
     }
 
-    fn multiplication(&mut self) -> Option<Expr> {
+    fn multiplication(&mut self) -> ParseResult<Expr> {
         let mut expr = self.unary()?;
 
-        if self.token_list.peek() == None {
-            return Some(expr);
-        }
-        while let TokenType::Slash | TokenType::Star = self.token_list.peek()?.t_type {
-            let operator = self.token_list.next()?.clone();
+        while let TokenType::Slash | TokenType::Star | TokenType::Percent = self.peek_type() {
+            let operator = self.advance().unwrap();
             let right = Box::new(self.unary()?);
-            let left = Box::new(expr);
             expr = Expr::Binary(Binary {
-                left,
+                left: Box::new(expr),
                 right,
                 operator,
             });
-            if self.token_list.peek() == None {
-                break;
-            }
         }
 
-        Some(expr)
+        Ok(expr)
     }
 
-    fn unary(&mut self) -> Option<Expr> {
-        let peek = self.token_list.peek()?;
-
-        if let TokenType::Bang | TokenType::Minus = peek.t_type {
-            let operator = self.token_list.next()?.clone();
+    fn unary(&mut self) -> ParseResult<Expr> {
+        if let TokenType::PlusPlus | TokenType::MinusMinus = self.peek_type() {
+            let operator = self.advance().unwrap();
+            let target = self.unary()?;
+            let one = Parser::one(&operator);
+            return Parser::update(target, operator, one, false);
+        }
+        if let TokenType::Bang | TokenType::Minus | TokenType::Tilde = self.peek_type() {
+            let operator = self.advance().unwrap();
             let expr = Box::new(self.unary()?);
-            Some(Expr::Unary(Unary { expr, operator }))
+            Ok(Expr::Unary(Unary { expr, operator }))
         } else {
-            self.primary()
+            self.exponent()
+        }
+    }
+
+    // `**` binds tighter than a unary operator on its left and is right
+    // associative, so `-2 ** 2` is -4 and `2 ** 3 ** 2` is 512.
+    fn exponent(&mut self) -> ParseResult<Expr> {
+        let expr = self.call()?;
+
+        if !self.check(TokenType::StarStar) {
+            return Ok(expr);
+        }
+        let operator = self.advance().unwrap();
+        let right = Box::new(self.unary()?);
+        Ok(Expr::Binary(Binary {
+            left: Box::new(expr),
+            right,
+            operator,
+        }))
+    }
+
+    fn call(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.primary()?;
+
+        loop {
+            match self.peek_type() {
+                TokenType::LeftParen => {
+                    self.advance();
+                    expr = self.finish_call(expr)?;
+                }
+                TokenType::Dot => {
+                    self.advance();
+                    let name =
+                        self.consume(TokenType::Identifier, "Expect property name after '.'.")?;
+                    expr = Expr::Get(Get {
+                        object: Box::new(expr),
+                        name,
+                    });
+                }
+                TokenType::LeftBracket => {
+                    self.advance();
+                    let index = self.expression()?;
+                    let bracket =
+                        self.consume(TokenType::RightBracket, "Expect ']' after index.")?;
+                    expr = Expr::Index(Index {
+                        object: Box::new(expr),
+                        index: Box::new(index),
+                        bracket,
+                    });
+                }
+                _ => break,
+            }
+        }
+
+        if let TokenType::PlusPlus | TokenType::MinusMinus = self.peek_type() {
+            let operator = self.advance().unwrap();
+            let one = Parser::one(&operator);
+            return Parser::update(expr, operator, one, true);
+        }
+
+        Ok(expr)
+    }
+
+    fn finish_call(&mut self, callee: Expr) -> ParseResult<Expr> {
+        let mut arguments = vec![];
+        if !self.check(TokenType::RightParen) {
+            loop {
+                arguments.push(self.expression()?);
+                if !self.check(TokenType::Comma) {
+                    break;
+                }
+                self.advance();
+            }
         }
+
+        let paren = self.consume(TokenType::RightParen, "Expect ')' after arguments.")?;
+
+        Ok(Expr::Call(Call {
+            callee: Box::new(callee),
+            paren,
+            arguments,
+        }))
     }
 
-    fn primary(&mut self) -> Option<Expr> {
-        let peek = self.token_list.peek()?;
+    // "a${b}c" arrives as Interpolation("a"), the tokens of `b`, String("c")
+    // and becomes "a" + stringify(b) + "c".
+    fn interpolation(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.string_part();
+
+        loop {
+            let value = self.expression()?;
+            let plus = Token::new(
+                TokenType::Plus,
+                "+".into(),
+                token::Literal::None,
+                self.last_line,
+            )
+            .with_span(value.span());
+            expr = Expr::Binary(Binary {
+                left: Box::new(expr),
+                right: Box::new(Expr::Stringify(Stringify {
+                    expr: Box::new(value),
+                })),
+                operator: plus.clone(),
+            });
 
-        match peek.t_type {
+            let is_last = self.check(TokenType::String);
+            if !is_last && !self.check(TokenType::Interpolation) {
+                return Err(self.error("Expect '}' after interpolated expression."));
+            }
+            expr = Expr::Binary(Binary {
+                left: Box::new(expr),
+                right: Box::new(self.string_part()),
+                operator: plus,
+            });
+            if is_last {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn string_part(&mut self) -> Expr {
+        let mut token = self.advance().unwrap();
+        token.t_type = TokenType::String;
+        Expr::Literal(Literal { token })
+    }
+
+    fn list(&mut self) -> ParseResult<Expr> {
+        let left_bracket = self.consume(TokenType::LeftBracket, "Expect '['.")?;
+
+        let mut elements = vec![];
+        if !self.check(TokenType::RightBracket) {
+            loop {
+                elements.push(self.expression()?);
+                if !self.check(TokenType::Comma) {
+                    break;
+                }
+                self.advance();
+            }
+        }
+
+        let right_bracket =
+            self.consume(TokenType::RightBracket, "Expect ']' after list elements.")?;
+
+        Ok(Expr::List(List {
+            left_bracket,
+            elements,
+            right_bracket,
+        }))
+    }
+
+    fn map(&mut self) -> ParseResult<Expr> {
+        let left_brace = self.consume(TokenType::LeftBrace, "Expect '{'.")?;
+
+        let mut entries = vec![];
+        if !self.check(TokenType::RightBrace) {
+            loop {
+                let key = self.expression()?;
+                self.consume(TokenType::Colon, "Expect ':' after map key.")?;
+                entries.push((key, self.expression()?));
+                if !self.check(TokenType::Comma) {
+                    break;
+                }
+                self.advance();
+            }
+        }
+
+        let right_brace = self.consume(TokenType::RightBrace, "Expect '}' after map entries.")?;
+
+        Ok(Expr::Map(Map {
+            left_brace,
+            entries,
+            right_brace,
+        }))
+    }
+
+    fn primary(&mut self) -> ParseResult<Expr> {
+        match self.peek_type() {
             TokenType::Identifier => {
-                let name = self.token_list.next()?.clone();
-                Some(Expr::Var(Var { name }))
+                let name = self.advance().unwrap();
+                Ok(Expr::Var(Var { name, depth: None }))
+            }
+            TokenType::This => {
+                let keyword = self.advance().unwrap();
+                Ok(Expr::This(This {
+                    keyword,
+                    depth: None,
+                }))
+            }
+            TokenType::Super => {
+                let keyword = self.advance().unwrap();
+                self.consume(TokenType::Dot, "Expect '.' after 'super'.")?;
+                let method =
+                    self.consume(TokenType::Identifier, "Expect superclass method name.")?;
+                Ok(Expr::Super(Super {
+                    keyword,
+                    method,
+                    depth: None,
+                }))
             }
             TokenType::Number
             | TokenType::String
             | TokenType::False
             | TokenType::True
             | TokenType::Nil => {
-                let token = self.token_list.next()?.clone();
-                Some(Expr::Literal(Literal { token }))
+                let token = self.advance().unwrap();
+                Ok(Expr::Literal(Literal { token }))
             }
             TokenType::LeftParen => {
-                self.token_list.next();
+                self.advance();
                 let expr = self.expression()?;
-                if let TokenType::RightParen = self.token_list.next().unwrap().t_type {
-                    return Some(Expr::Grouping(Grouping {
-                        expr: Box::new(expr),
-                    }));
-                }
-                self.error = true;
-                println!("Expecting ')' after '(' and expression");
-                None
-            }
-            TokenType::EOF | TokenType::Semicolon => None,
-            _ => {
-                self.error = true;
-                println!("Token not supported on primary - Expecting an expression");
-                println!("{:?}", peek.line);
-                None
+                self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
+                Ok(Expr::Grouping(Grouping {
+                    expr: Box::new(expr),
+                }))
             }
+            TokenType::LeftBracket => self.list(),
+            TokenType::Interpolation => self.interpolation(),
+            // A `{` starting a statement is always a block, so a map literal
+            // can only turn up here, where an expression is expected.
+            TokenType::LeftBrace => self.map(),
+            _ => Err(self.error("Expect expression.")),
         }
     }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.token_list.next()?;
+        self.last_line = token.line;
+        Some(token.clone())
+    }
+
+    // Running out of tokens is treated the same as reaching EOF.
+    fn peek_type(&mut self) -> TokenType {
+        match self.token_list.peek() {
+            Some(token) => token.t_type,
+            None => TokenType::EOF,
+        }
+    }
+
+    fn peek_lexeme(&mut self) -> Option<&str> {
+        self.token_list.peek().map(|token| token.lexeme.as_str())
+    }
+
+    fn check(&mut self, t_type: TokenType) -> bool {
+        self.peek_type() == t_type
+    }
+
+    fn is_at_end(&mut self) -> bool {
+        self.check(TokenType::EOF)
+    }
+
+    fn consume(&mut self, t_type: TokenType, message: &str) -> ParseResult<Token> {
+        if self.check(t_type) {
+            return Ok(self.advance().unwrap());
+        }
+        Err(self.error(message))
+    }
+
+    fn error(&mut self, message: &str) -> ParseError {
+        let token = match self.token_list.peek() {
+            Some(token) => (*token).clone(),
+            None => Token::empty_token(self.last_line),
+        };
+        ParseError::new(token, message)
+    }
 }
 
 #[cfg(test)]
@@ -561,7 +1122,7 @@ mod tests {
         let tokens = vec![string_token, semicolon];
 
         let mut parser = Parser::new(&tokens);
-        let mut stmt = parser.parse();
+        let (mut stmt, _) = parser.parse();
         if let Some(Stmt::Expr(expr)) = stmt.pop() {
             assert_eq!(expr, expected_expr);
         } else {
@@ -586,7 +1147,7 @@ mod tests {
         let tokens = vec![operator, number, semicolon];
 
         let mut parser = Parser::new(&tokens);
-        let mut stmt = parser.parse();
+        let (mut stmt, _) = parser.parse();
         if let Some(Stmt::Expr(expr)) = stmt.pop() {
             assert_eq!(expr, expected_expr);
         } else {
@@ -616,7 +1177,7 @@ mod tests {
         let tokens = vec![first_number, operator, second_number, semicolon];
 
         let mut parser = Parser::new(&tokens);
-        let mut stmt = parser.parse();
+        let (mut stmt, _) = parser.parse();
         if let Some(Stmt::Expr(expr)) = stmt.pop() {
             assert_eq!(expr, expected_expr);
         } else {
@@ -646,7 +1207,7 @@ mod tests {
         let tokens = vec![first_number, operator, second_number, semicolon];
 
         let mut parser = Parser::new(&tokens);
-        let mut stmt = parser.parse();
+        let (mut stmt, _) = parser.parse();
         if let Some(Stmt::Expr(expr)) = stmt.pop() {
             assert_eq!(expr, expected_expr);
         } else {
@@ -676,7 +1237,7 @@ mod tests {
         let tokens = vec![first_number, operator, second_number, semicolon];
 
         let mut parser = Parser::new(&tokens);
-        let mut stmt = parser.parse();
+        let (mut stmt, _) = parser.parse();
         if let Some(Stmt::Expr(expr)) = stmt.pop() {
             assert_eq!(expr, expected_expr);
         } else {
@@ -706,7 +1267,7 @@ mod tests {
         let tokens = vec![first_number, operator, second_number, semicolon];
 
         let mut parser = Parser::new(&tokens);
-        let mut stmt = parser.parse();
+        let (mut stmt, _) = parser.parse();
         if let Some(Stmt::Expr(expr)) = stmt.pop() {
             assert_eq!(expr, expected_expr);
         } else {
@@ -720,9 +1281,7 @@ mod tests {
         // should parse as:
         // {
         //  var a = 1;
-        //  while(a < 2) {
-        //   a = a + 1;
-        //  }
+        //  while(a < 2) {} with increment a = a + 1
         // }
         let for_kw = Token::new(TokenType::For, "for".to_owned(), Literal::None, 1);
         let left_paren = Token::new(TokenType::LeftParen, "(".to_owned(), Literal::None, 1);
@@ -770,10 +1329,11 @@ mod tests {
 
         let declaration = Stmt::Declaration(stmt::Var {
             value: Expr::Literal(super::Literal { token: one.clone() }),
-            name: 'a'.to_string(),
+            name: variable.clone(),
         });
         let while_left = Expr::Var(super::Var {
             name: variable.clone(),
+            depth: None,
         });
         let while_right = Expr::Literal(super::Literal { token: two.clone() });
         let while_greater_expr = Expr::Binary(Binary {
@@ -784,6 +1344,7 @@ mod tests {
 
         let block_var = Expr::Var(super::Var {
             name: variable.clone(),
+            depth: None,
         });
         let block_value = Expr::Literal(super::Literal { token: one.clone() });
         let block_right = Expr::Binary(Binary {
@@ -791,19 +1352,18 @@ mod tests {
             right: Box::new(block_value),
             operator: plus_sign.clone(),
         });
-        let block_left = Stmt::Expr(Expr::Assignment(super::Assignment {
+        let increment = Expr::Assignment(super::Assignment {
             name: variable,
             value: Box::new(block_right),
-        }));
-
-        let inner_block_for = Stmt::Block(super::Block { stmt_vec: vec![] });
-        let block = Stmt::Block(super::Block {
-            stmt_vec: vec![inner_block_for, block_left],
+            depth: None,
         });
 
+        let block = Stmt::Block(super::Block { stmt_vec: vec![] });
+
         let while_stmt = Stmt::While(While {
             condition: while_greater_expr,
             body: Box::new(block),
+            increment: Some(increment),
         });
 
         let desugared_for = Stmt::Block(super::Block {
@@ -811,7 +1371,7 @@ mod tests {
         });
 
         let mut parser = Parser::new(&tokens);
-        let mut stmt = parser.parse();
+        let (mut stmt, _) = parser.parse();
         assert_eq!(desugared_for, stmt.pop().unwrap());
     }
     #[test]
@@ -853,6 +1413,7 @@ mod tests {
 
         let while_left = Expr::Var(super::Var {
             name: variable.clone(),
+            depth: None,
         });
         let while_right = Expr::Literal(super::Literal { token: two.clone() });
         let while_greater_expr = Expr::Binary(Binary {
@@ -863,6 +1424,7 @@ mod tests {
 
         let block_var = Expr::Var(super::Var {
             name: variable.clone(),
+            depth: None,
         });
         let block_value = Expr::Literal(super::Literal { token: one.clone() });
         let block_right = Expr::Binary(Binary {
@@ -873,6 +1435,7 @@ mod tests {
         let block_left = Stmt::Expr(Expr::Assignment(super::Assignment {
             name: variable,
             value: Box::new(block_right),
+            depth: None,
         }));
 
         let block = Stmt::Block(super::Block {
@@ -882,10 +1445,305 @@ mod tests {
         let while_stmt = Stmt::While(While {
             condition: while_greater_expr,
             body: Box::new(block),
+            increment: None,
         });
 
         let mut parser = Parser::new(&tokens);
-        let mut stmt = parser.parse();
+        let (mut stmt, _) = parser.parse();
         assert_eq!(while_stmt, stmt.pop().unwrap());
     }
+
+    #[test]
+    fn function_declaration_and_call_parse() {
+        // fun id(a) { return a; }
+        // id(1);
+        let fun_kw = Token::new(TokenType::Fun, "fun".to_owned(), Literal::None, 1);
+        let name = Token::new(TokenType::Identifier, "id".to_owned(), Literal::None, 1);
+        let left_paren = Token::new(TokenType::LeftParen, "(".to_owned(), Literal::None, 1);
+        let param = Token::new(TokenType::Identifier, "a".to_owned(), Literal::None, 1);
+        let right_paren = Token::new(TokenType::RightParen, ")".to_owned(), Literal::None, 1);
+        let left_bracket = Token::new(TokenType::LeftBrace, "{".to_owned(), Literal::None, 1);
+        let return_kw = Token::new(TokenType::Return, "return".to_owned(), Literal::None, 1);
+        let semicolon = Token::new(TokenType::Semicolon, ";".to_owned(), Literal::None, 1);
+        let right_bracket = Token::new(TokenType::RightBrace, "}".to_owned(), Literal::None, 1);
+        let one = Token::new(TokenType::Number, "1".to_owned(), Literal::F64(1.0), 2);
+
+        let tokens = vec![
+            fun_kw,
+            name.clone(),
+            left_paren.clone(),
+            param.clone(),
+            right_paren.clone(),
+            left_bracket,
+            return_kw.clone(),
+            param.clone(),
+            semicolon.clone(),
+            right_bracket,
+            name.clone(),
+            left_paren,
+            one.clone(),
+            right_paren.clone(),
+            semicolon,
+        ];
+
+        let function = Stmt::Function(Rc::new(Function {
+            name: name.clone(),
+            params: vec![param.clone()],
+            body: vec![Stmt::Return(Return {
+                keyword: return_kw,
+                value: Some(Expr::Var(super::Var {
+                    name: param,
+                    depth: None,
+                })),
+            })],
+        }));
+        let call = Stmt::Expr(Expr::Call(Call {
+            callee: Box::new(Expr::Var(super::Var { name, depth: None })),
+            paren: right_paren,
+            arguments: vec![Expr::Literal(super::Literal { token: one })],
+        }));
+
+        let mut parser = Parser::new(&tokens);
+        let (stmts, _) = parser.parse();
+        assert_eq!(vec![function, call], stmts);
+    }
+
+    #[test]
+    fn property_set_parse() {
+        // a.b = 1;
+        let object = Token::new(TokenType::Identifier, "a".to_owned(), Literal::None, 1);
+        let dot = Token::new(TokenType::Dot, ".".to_owned(), Literal::None, 1);
+        let name = Token::new(TokenType::Identifier, "b".to_owned(), Literal::None, 1);
+        let equal_sign = Token::new(TokenType::Equal, "=".to_owned(), Literal::None, 1);
+        let one = Token::new(TokenType::Number, "1".to_owned(), Literal::F64(1.0), 1);
+        let semicolon = Token::new(TokenType::Semicolon, ";".to_owned(), Literal::None, 1);
+
+        let tokens = vec![
+            object.clone(),
+            dot,
+            name.clone(),
+            equal_sign,
+            one.clone(),
+            semicolon,
+        ];
+
+        let expected_expr = Expr::Set(Set {
+            object: Box::new(Expr::Var(super::Var {
+                name: object,
+                depth: None,
+            })),
+            name,
+            value: Box::new(Expr::Literal(super::Literal { token: one })),
+        });
+
+        let mut parser = Parser::new(&tokens);
+        let (mut stmt, _) = parser.parse();
+        assert_eq!(Some(Stmt::Expr(expected_expr)), stmt.pop());
+    }
+
+    #[test]
+    fn reports_every_error_and_keeps_parsing() {
+        let source =
+            "var a = ;\nprint 1;\nprint (2;\nvar b = 3;\nfun f() {\n var = 1;\n print 2;\n { print }\n}\nprint 3;";
+        let mut scanner = crate::lox::scanner::Scanner::new(source);
+        let (tokens, _) = scanner.scan_text();
+        let mut parser = Parser::new(&tokens);
+        let (stmts, errors) = parser.parse();
+
+        let messages: Vec<(u32, &str)> = errors
+            .iter()
+            .map(|error| (error.token.line, error.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                (1, "Expect expression."),
+                (3, "Expect ')' after expression."),
+                (6, "Expect variable name."),
+                (8, "Expect expression.")
+            ]
+        );
+        assert_eq!(stmts.len(), 4);
+        match &stmts[2] {
+            Stmt::Function(function) => assert_eq!(function.body.len(), 2),
+            stmt => panic!("Expected a function, got {:?}", stmt),
+        }
+    }
+
+    #[test]
+    fn invalid_assignment_target() {
+        let one = Token::new(TokenType::Number, "1".to_owned(), Literal::F64(1.0), 1);
+        let equal_sign = Token::new(TokenType::Equal, "=".to_owned(), Literal::None, 1);
+        let two = Token::new(TokenType::Number, "2".to_owned(), Literal::F64(2.0), 1);
+        let semicolon = Token::new(TokenType::Semicolon, ";".to_owned(), Literal::None, 1);
+        let tokens = vec![one, equal_sign.clone(), two, semicolon];
+
+        let mut parser = Parser::new(&tokens);
+        let (stmts, errors) = parser.parse();
+        assert_eq!(stmts, vec![]);
+        assert_eq!(
+            errors,
+            vec![ParseError::new(equal_sign, "Invalid assignment target.")]
+        );
+    }
+
+    #[test]
+    fn missing_token_at_end() {
+        let print_kw = Token::new(TokenType::Print, "print".to_owned(), Literal::None, 1);
+        let one = Token::new(TokenType::Number, "1".to_owned(), Literal::F64(1.0), 1);
+        let eof = Token::new(TokenType::EOF, "".to_owned(), Literal::None, 1);
+        let tokens = vec![print_kw, one, eof];
+
+        let mut parser = Parser::new(&tokens);
+        let (_, errors) = parser.parse();
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec!["[line 1] Error at end: Expect ';' after value."]
+        );
+    }
+
+    #[test]
+    fn break_and_continue_outside_a_loop() {
+        let source = "break;\nwhile (true) { fun f() { continue; } break; }\nprint 1;";
+        let mut scanner = crate::lox::scanner::Scanner::new(source);
+        let (tokens, _) = scanner.scan_text();
+        let mut parser = Parser::new(&tokens);
+        let (stmts, errors) = parser.parse();
+
+        let messages: Vec<(u32, &str)> = errors
+            .iter()
+            .map(|error| (error.token.line, error.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                (1, "Can't use 'break' outside of a loop."),
+                (2, "Can't use 'continue' outside of a loop.")
+            ]
+        );
+        assert_eq!(
+            stmts.last(),
+            Some(&Stmt::Print(Expr::Literal(super::Literal {
+                token: Token::new(TokenType::Number, "1".to_owned(), Literal::Int(1), 3)
+            })))
+        );
+    }
+
+    #[test]
+    fn braces_are_blocks_at_statement_start_and_maps_otherwise() {
+        let source = "{ print 1; }\nvar m = {\"a\": 1, 2: {}};";
+        let mut scanner = crate::lox::scanner::Scanner::new(source);
+        let (tokens, _) = scanner.scan_text();
+        let mut parser = Parser::new(&tokens);
+        let (stmts, errors) = parser.parse();
+        assert_eq!(errors, vec![]);
+
+        assert!(matches!(stmts[0], Stmt::Block(_)));
+        match &stmts[1] {
+            Stmt::Declaration(stmt::Var {
+                value: Expr::Map(map),
+                ..
+            }) => {
+                assert_eq!(map.entries.len(), 2);
+                assert!(matches!(map.entries[1].1, Expr::Map(_)));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn invalid_update_targets() {
+        let source = "1 += 2;\n++1;\n(a)++;\na.b++;";
+        let mut scanner = crate::lox::scanner::Scanner::new(source);
+        let (tokens, _) = scanner.scan_text();
+        let mut parser = Parser::new(&tokens);
+        let (stmts, errors) = parser.parse();
+
+        let messages: Vec<(u32, &str)> = errors
+            .iter()
+            .map(|error| (error.token.line, error.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                (1, "Invalid assignment target."),
+                (2, "Invalid assignment target."),
+                (3, "Invalid assignment target.")
+            ]
+        );
+        assert!(matches!(
+            &stmts[..],
+            [Stmt::Expr(Expr::Update(Update { postfix: true, .. }))]
+        ));
+    }
+
+    #[test]
+    fn ternary_is_right_associative() {
+        let source = "a ? b : c ? d : e;\na ? b;";
+        let mut scanner = crate::lox::scanner::Scanner::new(source);
+        let (tokens, _) = scanner.scan_text();
+        let mut parser = Parser::new(&tokens);
+        let (stmts, errors) = parser.parse();
+
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec!["[line 2] Error at ';': Expect ':' after then branch of conditional."]
+        );
+        match &stmts[0] {
+            Stmt::Expr(Expr::Ternary(ternary)) => {
+                assert!(matches!(*ternary.condition, Expr::Var(_)));
+                assert!(matches!(*ternary.else_branch, Expr::Ternary(_)));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn try_needs_catch_or_finally() {
+        let source = "try { print 1; } print 2;\ntry {} catch e {}\ntry {} finally {}";
+        let mut scanner = crate::lox::scanner::Scanner::new(source);
+        let (tokens, _) = scanner.scan_text();
+        let mut parser = Parser::new(&tokens);
+        let (stmts, errors) = parser.parse();
+
+        let messages: Vec<(u32, &str)> = errors
+            .iter()
+            .map(|error| (error.token.line, error.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                (1, "Expect 'catch' or 'finally' after try block."),
+                (2, "Expect '(' after 'catch'."),
+            ]
+        );
+        assert_eq!(stmts.len(), 1);
+    }
+
+    #[test]
+    fn import_names() {
+        let source =
+            "import \"lib/utils.lox\";\nimport \"my-lib.lox\" as lib;\nimport \"my-lib.lox\";";
+        let mut scanner = crate::lox::scanner::Scanner::new(source);
+        let (tokens, _) = scanner.scan_text();
+        let mut parser = Parser::new(&tokens);
+        let (stmts, errors) = parser.parse();
+
+        let names: Vec<&str> = stmts
+            .iter()
+            .map(|stmt| match stmt {
+                Stmt::Import(import) => import.name.lexeme.as_str(),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(names, vec!["utils", "lib"]);
+        let messages: Vec<(u32, &str)> = errors
+            .iter()
+            .map(|error| (error.token.line, error.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![(3, "Expect 'as' and a name for this module.")]
+        );
+    }
 }
@@ -0,0 +1,447 @@
+use super::expr::Expr;
+use super::stmt::{self, Stmt};
+use super::token::{Span, Token};
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+enum FunctionType {
+    None,
+    Function,
+    Method,
+    Initializer,
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct ResolveError {
+    pub token: Box<Token>,
+    pub message: String,
+    pub span: Span,
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[line {}] Error at '{}': {}",
+            self.token.line, self.token.lexeme, self.message
+        )
+    }
+}
+
+// Walks the statements once before they are run and records, on every
+// variable expression, how many environments away its declaration lives.
+// Expressions left with no depth are globals.
+#[derive(Debug)]
+pub struct Resolver {
+    scopes: Vec<HashMap<String, bool>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    errors: Vec<ResolveError>,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self {
+            scopes: vec![],
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            errors: vec![],
+        }
+    }
+
+    pub fn resolve(&mut self, statements: &mut [Stmt]) -> Result<(), Vec<ResolveError>> {
+        self.resolve_stmts(statements);
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    fn resolve_stmts(&mut self, statements: &mut [Stmt]) {
+        for stmt in statements {
+            self.resolve_stmt(stmt);
+        }
+    }
+
+    fn resolve_stmt(&mut self, stmt: &mut Stmt) {
+        match stmt {
+            Stmt::Block(block) => {
+                self.begin_scope();
+                self.resolve_stmts(&mut block.stmt_vec);
+                self.end_scope();
+            }
+            Stmt::Declaration(var) => {
+                self.declare(&var.name);
+                self.resolve_expr(&mut var.value);
+                self.define(&var.name);
+            }
+            Stmt::Function(function) => {
+                self.declare(&function.name);
+                self.define(&function.name);
+                self.resolve_function(Rc::make_mut(function), FunctionType::Function);
+            }
+            Stmt::Class(class) => self.resolve_class(class),
+            Stmt::Expr(expr) | Stmt::Print(expr) => self.resolve_expr(expr),
+            Stmt::If(if_stmt) => {
+                self.resolve_expr(&mut if_stmt.condition);
+                self.resolve_stmt(&mut if_stmt.truth_branch);
+                if let Some(false_branch) = if_stmt.false_branch.as_mut() {
+                    self.resolve_stmt(false_branch);
+                }
+            }
+            Stmt::While(while_stmt) => {
+                self.resolve_expr(&mut while_stmt.condition);
+                self.resolve_stmt(&mut while_stmt.body);
+                if let Some(increment) = &mut while_stmt.increment {
+                    self.resolve_expr(increment);
+                }
+            }
+            Stmt::Return(return_stmt) => self.resolve_return(return_stmt),
+            Stmt::Break(_) | Stmt::Continue(_) => (),
+            Stmt::Defer(defer) => {
+                if self.scopes.is_empty() {
+                    self.error(&defer.keyword, "Can't use 'defer' outside of a block.");
+                }
+                self.resolve_stmt(&mut defer.body);
+            }
+            Stmt::Import(import) => {
+                self.declare(&import.name);
+                self.define(&import.name);
+            }
+            Stmt::Throw(throw) => self.resolve_expr(&mut throw.value),
+            Stmt::Try(try_stmt) => {
+                self.begin_scope();
+                self.resolve_stmts(&mut try_stmt.body);
+                self.end_scope();
+                if let Some(catch) = &mut try_stmt.catch {
+                    self.begin_scope();
+                    self.declare(&catch.name);
+                    self.define(&catch.name);
+                    self.resolve_stmts(&mut catch.body);
+                    self.end_scope();
+                }
+                if let Some(finally) = &mut try_stmt.finally {
+                    self.begin_scope();
+                    self.resolve_stmts(finally);
+                    self.end_scope();
+                }
+            }
+        }
+    }
+
+    fn resolve_return(&mut self, return_stmt: &mut stmt::Return) {
+        if self.current_function == FunctionType::None {
+            self.error(&return_stmt.keyword, "Can't return from top-level code.");
+        }
+        if let Some(value) = &mut return_stmt.value {
+            if self.current_function == FunctionType::Initializer {
+                self.error(
+                    &return_stmt.keyword,
+                    "Can't return a value from an initializer.",
+                );
+            }
+            self.resolve_expr(value);
+        }
+    }
+
+    fn resolve_class(&mut self, class: &mut stmt::Class) {
+        let enclosing_class = self.current_class;
+        self.current_class = ClassType::Class;
+
+        self.declare(&class.name);
+        self.define(&class.name);
+
+        if let Some(superclass) = &mut class.superclass {
+            if superclass.name.lexeme == class.name.lexeme {
+                self.error(&superclass.name, "A class can't inherit from itself.");
+            }
+            self.current_class = ClassType::Subclass;
+            superclass.depth = self.resolve_local(&superclass.name);
+
+            self.begin_scope();
+            self.define_name("super");
+        }
+
+        self.begin_scope();
+        self.define_name("this");
+        for method in &mut class.methods {
+            let function_type = if method.name.lexeme == "init" {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            self.resolve_function(Rc::make_mut(method), function_type);
+        }
+        self.end_scope();
+
+        if class.superclass.is_some() {
+            self.end_scope();
+        }
+        self.current_class = enclosing_class;
+    }
+
+    fn resolve_function(&mut self, function: &mut stmt::Function, function_type: FunctionType) {
+        let enclosing_function = self.current_function;
+        self.current_function = function_type;
+
+        self.begin_scope();
+        for param in &function.params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve_stmts(&mut function.body);
+        self.end_scope();
+
+        self.current_function = enclosing_function;
+    }
+
+    fn resolve_expr(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Var(var) => {
+                let in_own_initializer = self
+                    .scopes
+                    .last()
+                    .and_then(|scope| scope.get(&var.name.lexeme))
+                    == Some(&false);
+                if in_own_initializer {
+                    self.error(
+                        &var.name,
+                        "Can't read local variable in its own initializer.",
+                    );
+                }
+                var.depth = self.resolve_local(&var.name);
+            }
+            Expr::Assignment(assignment) => {
+                self.resolve_expr(&mut assignment.value);
+                assignment.depth = self.resolve_local(&assignment.name);
+            }
+            Expr::Binary(binary) => {
+                self.resolve_expr(&mut binary.left);
+                self.resolve_expr(&mut binary.right);
+            }
+            Expr::Logical(logical) => {
+                self.resolve_expr(&mut logical.left);
+                self.resolve_expr(&mut logical.right);
+            }
+            Expr::Unary(unary) => self.resolve_expr(&mut unary.expr),
+            Expr::Grouping(grouping) => self.resolve_expr(&mut grouping.expr),
+            Expr::Stringify(stringify) => self.resolve_expr(&mut stringify.expr),
+            Expr::Ternary(ternary) => {
+                self.resolve_expr(&mut ternary.condition);
+                self.resolve_expr(&mut ternary.then_branch);
+                self.resolve_expr(&mut ternary.else_branch);
+            }
+            Expr::Update(update) => {
+                self.resolve_expr(&mut update.target);
+                self.resolve_expr(&mut update.value);
+            }
+            Expr::Literal(_) => (),
+            Expr::Call(call) => {
+                self.resolve_expr(&mut call.callee);
+                for argument in &mut call.arguments {
+                    self.resolve_expr(argument);
+                }
+            }
+            Expr::Get(get) => self.resolve_expr(&mut get.object),
+            Expr::List(list) => {
+                for element in &mut list.elements {
+                    self.resolve_expr(element);
+                }
+            }
+            Expr::Map(map) => {
+                for (key, value) in &mut map.entries {
+                    self.resolve_expr(key);
+                    self.resolve_expr(value);
+                }
+            }
+            Expr::Index(index) => {
+                self.resolve_expr(&mut index.object);
+                self.resolve_expr(&mut index.index);
+            }
+            Expr::SetIndex(set) => {
+                self.resolve_expr(&mut set.object);
+                self.resolve_expr(&mut set.index);
+                self.resolve_expr(&mut set.value);
+            }
+            Expr::Set(set) => {
+                self.resolve_expr(&mut set.value);
+                self.resolve_expr(&mut set.object);
+            }
+            Expr::This(this) => {
+                if self.current_class == ClassType::None {
+                    self.error(&this.keyword, "Can't use 'this' outside of a class.");
+                }
+                this.depth = self.resolve_local(&this.keyword);
+            }
+            Expr::Super(super_expr) => match self.current_class {
+                ClassType::None => {
+                    self.error(&super_expr.keyword, "Can't use 'super' outside of a class.")
+                }
+                ClassType::Class => self.error(
+                    &super_expr.keyword,
+                    "Can't use 'super' in a class with no superclass.",
+                ),
+                ClassType::Subclass => {
+                    super_expr.depth = self.resolve_local(&super_expr.keyword);
+                }
+            },
+        }
+    }
+
+    fn resolve_local(&self, name: &Token) -> Option<usize> {
+        self.scopes
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(&name.lexeme))
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        let already_declared = match self.scopes.last_mut() {
+            None => return,
+            Some(scope) => scope.insert(name.lexeme.clone(), false).is_some(),
+        };
+        if already_declared {
+            self.error(name, "Already a variable with this name in this scope.");
+        }
+    }
+
+    fn define(&mut self, name: &Token) {
+        self.define_name(&name.lexeme);
+    }
+
+    fn define_name(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.into(), true);
+        }
+    }
+
+    fn error(&mut self, token: &Token, message: &str) {
+        self.errors.push(ResolveError {
+            token: Box::new(token.clone()),
+            message: message.into(),
+            span: token.span,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lox::parser::Parser;
+    use crate::lox::scanner::Scanner;
+    use pretty_assertions::assert_eq;
+
+    fn resolve(source: &str) -> (Vec<Stmt>, Result<(), Vec<ResolveError>>) {
+        let mut scanner = Scanner::new(source);
+        let (tokens, _) = scanner.scan_text();
+        let mut parser = Parser::new(&tokens);
+        let (mut statements, errors) = parser.parse();
+        assert_eq!(errors, vec![]);
+        let result = Resolver::new().resolve(&mut statements);
+        (statements, result)
+    }
+
+    fn messages(result: Result<(), Vec<ResolveError>>) -> Vec<String> {
+        result
+            .unwrap_err()
+            .into_iter()
+            .map(|error| error.message)
+            .collect()
+    }
+
+    #[test]
+    fn local_depths() {
+        let (statements, result) = resolve("var a = 1; { var b = a; { b; } }");
+        assert_eq!(result, Ok(()));
+
+        let outer = match &statements[1] {
+            Stmt::Block(block) => &block.stmt_vec,
+            _ => unreachable!(),
+        };
+        match &outer[0] {
+            Stmt::Declaration(var) => match &var.value {
+                Expr::Var(global) => assert_eq!(global.depth, None),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        }
+        if let Stmt::Block(inner) = &outer[1] {
+            match &inner.stmt_vec[0] {
+                Stmt::Expr(Expr::Var(var)) => assert_eq!(var.depth, Some(1)),
+                _ => unreachable!(),
+            }
+        } else {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn reading_local_in_own_initializer() {
+        let (_, result) = resolve("{ var a = a; }");
+        assert_eq!(
+            messages(result),
+            vec!["Can't read local variable in its own initializer."]
+        );
+    }
+
+    #[test]
+    fn duplicate_declaration_in_scope() {
+        let (_, result) = resolve("{ var a = 1; var a = 2; }");
+        assert_eq!(
+            messages(result),
+            vec!["Already a variable with this name in this scope."]
+        );
+    }
+
+    #[test]
+    fn invalid_return_and_this() {
+        let (_, result) = resolve("return 1; print this;");
+        assert_eq!(
+            messages(result),
+            vec![
+                "Can't return from top-level code.",
+                "Can't use 'this' outside of a class."
+            ]
+        );
+    }
+
+    #[test]
+    fn returning_a_value_from_an_initializer() {
+        let (_, result) = resolve("class A {\n  init() {\n    return 1;\n  }\n}");
+        let errors = result.unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].message,
+            "Can't return a value from an initializer."
+        );
+        assert_eq!(errors[0].token.lexeme, "return");
+        assert_eq!(errors[0].span, errors[0].token.span);
+    }
+
+    #[test]
+    fn defer_outside_a_block() {
+        let (_, result) = resolve("defer print 1; { defer print 2; }");
+        assert_eq!(
+            messages(result),
+            vec!["Can't use 'defer' outside of a block."]
+        );
+    }
+}
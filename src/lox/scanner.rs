@@ -1,6 +1,7 @@
-use super::token::{Literal, Token, TokenType};
+use super::token::{Literal, Span, Token, TokenType};
 use phf::phf_map;
 use std::char;
+use std::fmt;
 use std::iter::Peekable;
 use std::str::Chars;
 
@@ -9,21 +10,33 @@ static SINGLE_TOKEN_MAP: phf::Map<char, TokenType> = phf_map! {
     ')' => TokenType::RightParen,
     '{' => TokenType::LeftBrace,
     '}' => TokenType::RightBrace,
+    '[' => TokenType::LeftBracket,
+    ']' => TokenType::RightBracket,
     ',' => TokenType::Comma,
+    ':' => TokenType::Colon,
+    '?' => TokenType::Question,
     '.' => TokenType::Dot,
-    '-' => TokenType::Minus,
-    '+' => TokenType::Plus,
     ';' => TokenType::Semicolon,
-    '*' => TokenType::Star,
+    '%' => TokenType::Percent,
+    '&' => TokenType::Ampersand,
+    '|' => TokenType::Pipe,
+    '^' => TokenType::Caret,
+    '~' => TokenType::Tilde,
 };
 
 static KEYWORDS: phf::Map<&str, TokenType> = phf_map! {
     "and" =>    TokenType::And,
+    "break" =>  TokenType::Break,
+    "catch" =>  TokenType::Catch,
     "class" =>  TokenType::Class,
+    "continue" => TokenType::Continue,
+    "defer" =>  TokenType::Defer,
     "else" =>   TokenType::Else,
     "false" =>  TokenType::False,
+    "finally" => TokenType::Finally,
     "for" =>    TokenType::For,
     "fun" =>    TokenType::Fun,
+    "import" => TokenType::Import,
     "if" =>     TokenType::If,
     "nil" =>    TokenType::Nil,
     "or" =>     TokenType::Or,
@@ -31,7 +44,9 @@ static KEYWORDS: phf::Map<&str, TokenType> = phf_map! {
     "return" => TokenType::Return,
     "super" =>  TokenType::Super,
     "this" =>   TokenType::This,
+    "throw" =>  TokenType::Throw,
     "true" =>   TokenType::True,
+    "try" =>    TokenType::Try,
     "var" =>    TokenType::Var,
     "while" =>  TokenType::While,
 };
@@ -48,52 +63,99 @@ fn key_getter(identifier: &str) -> (bool, &str) {
     (contain_key, keyword_key)
 }
 
+#[derive(PartialEq, Debug, Clone)]
+pub struct ScanError {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+    pub span: Span,
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[line {}, column {}] Error: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
 pub struct Scanner<'a> {
     chars: Peekable<Chars<'a>>,
     tokens: Vec<Token>,
-    errors: Vec<usize>,
+    errors: Vec<ScanError>,
     line: usize,
+    column: usize,
+    offset: usize,
+    token_line: usize,
+    token_column: usize,
+    token_start: usize,
+    // Brace depth inside each `${` we are scanning, innermost last.
+    interpolations: Vec<usize>,
 }
 
 impl<'a> Scanner<'a> {
-    pub fn new(source: &str) -> Scanner {
+    pub fn new(source: &str) -> Scanner<'_> {
         Scanner {
             chars: source.chars().peekable(),
             tokens: vec![],
             errors: vec![],
             line: 1,
+            column: 1,
+            offset: 0,
+            token_line: 1,
+            token_column: 1,
+            token_start: 0,
+            interpolations: vec![],
         }
     }
 
-    pub fn scan_text(&mut self) -> &Vec<Token> {
-        while self.chars.peek() != None {
+    pub fn scan_text(&mut self) -> (Vec<Token>, Vec<ScanError>) {
+        while self.chars.peek().is_some() {
             self.scan_next_token();
         }
-        self.tokens.push(Token::empty_token(self.line as u32));
+        if !self.interpolations.is_empty() {
+            self.token_line = self.line;
+            self.token_column = self.column;
+            self.token_start = self.offset;
+            self.error("Unterminated string interpolation.");
+        }
+        let eof_span = Span::new(self.offset, self.offset, self.column);
+        self.tokens
+            .push(Token::empty_token(self.line as u32).with_span(eof_span));
+
+        (
+            std::mem::take(&mut self.tokens),
+            std::mem::take(&mut self.errors),
+        )
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let ch = self.chars.next()?;
+        self.offset += ch.len_utf8();
+        if ch == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(ch)
+    }
 
-        &self.tokens
+    fn peek_next(&self) -> Option<char> {
+        self.chars.clone().nth(1)
     }
 
     fn identifier(&mut self, first_digit: char) {
         let mut identifier = String::new();
         identifier.push(first_digit);
-        while self.chars.peek() != Some(&' ')
-            && self.chars.peek() != Some(&';')
-            && self.chars.peek() != None
-        {
-            if SINGLE_TOKEN_MAP.contains_key(self.chars.peek().unwrap()) {
+        while let Some(&ch) = self.chars.peek() {
+            if !(ch.is_alphanumeric() || ch == '_') {
                 break;
             }
-            match self.chars.peek().unwrap() {
-                'a'..='z' | 'A'..='Z' | '_' | '-' => {
-                    identifier.push(self.chars.next().unwrap());
-                }
-                ch => {
-                    println!("Unsupported char {} at line {}", ch, self.line);
-                    self.chars.next();
-                    self.errors.push(self.line);
-                }
-            }
+            identifier.push(ch);
+            self.advance();
         }
         let (contain_key, keyword_key) = key_getter(&identifier);
         if contain_key {
@@ -113,91 +175,146 @@ impl<'a> Scanner<'a> {
         let mut number = String::new();
         number.push(first_digit);
 
-        while self.chars.peek() != Some(&' ') && self.chars.peek() != None {
-            let chr = self.chars.peek().unwrap();
-            if chr == &'.' {
-                self.chars.next().unwrap();
-                number.push('.');
-                if self.chars.peek().unwrap_or(&' ').is_digit(10) {
-                    number.push(self.chars.next().unwrap());
-                } else {
-                    println!("Unterminated number at line: {}", self.line);
-                    self.errors.push(self.line);
-                }
-            } else if chr.is_digit(10) {
-                let digit = self.chars.next().unwrap();
-                number.push(digit);
-            } else {
-                let ch = self.chars.peek().unwrap();
-                if SINGLE_TOKEN_MAP.contains_key(&ch) {
-                    break;
-                } else {
-                    println!("Unterminated number started at line: {}", self.line);
-                    self.chars.next();
-                    self.errors.push(self.line);
+        while let Some(&ch) = self.chars.peek() {
+            let fraction = ch == '.' && self.peek_next().is_some_and(|next| next.is_ascii_digit());
+            if !ch.is_ascii_digit() && !fraction {
+                break;
+            }
+            number.push(ch);
+            self.advance();
+        }
+
+        // Literals without a fractional part are integers.
+        if !number.contains('.') {
+            return match number.parse::<i64>() {
+                Ok(parsed_number) => {
+                    self.add_token(TokenType::Number, number, Literal::Int(parsed_number))
                 }
+                Err(_) => self.error(format!("Integer literal {} is too large.", number)),
+            };
+        }
+
+        match number.parse::<f64>() {
+            Ok(parsed_number) => {
+                self.add_token(TokenType::Number, number, Literal::F64(parsed_number))
             }
+            Err(_) => self.error(format!("Invalid number {}.", number)),
         }
-        let parsed_number = number.parse::<f64>().unwrap();
-        self.add_token(TokenType::Number, number, Literal::F64(parsed_number))
     }
 
+    // Also used to carry on with a string after the `}` closing one of its
+    // interpolations, each `${` ends the current part with an Interpolation
+    // token and the expression inside is scanned as ordinary tokens.
     fn string(&mut self) {
-        let line_start = self.line;
-        let mut word = Vec::new();
+        let mut word = String::new();
 
-        while self.chars.peek() != Some(&'"') && self.chars.peek() != None {
-            match self.chars.next().unwrap() {
-                '\n' => self.line += 1,
+        loop {
+            match self.chars.peek() {
+                None => return self.error("Unterminated string."),
+                Some(&'"') => break,
+                Some(_) => (),
+            }
+            let (line, start, column) = (self.line, self.offset, self.column);
+            match self.advance().unwrap() {
+                '\n' => (),
+                '\\' if self.chars.peek().is_some() => match self.escape() {
+                    Ok(ch) => word.push(ch),
+                    Err(message) => self.error_at(message, line, start, column),
+                },
+                '$' if self.chars.peek() == Some(&'{') => {
+                    self.advance();
+                    self.interpolations.push(0);
+                    let literal = Literal::String(word.clone());
+                    return self.add_token(TokenType::Interpolation, word, literal);
+                }
                 next_char => word.push(next_char),
             }
         }
 
-        match self.chars.peek() {
-            Some(&'"') => {
-                self.chars.next();
-                let word_clone = word.clone();
-                self.add_token(
-                    TokenType::String,
-                    word.into_iter().collect::<String>(),
-                    Literal::String(word_clone.into_iter().collect::<String>()),
-                )
-            }
-            None => {
-                println!("Unterminated string started at line: {}", line_start);
-                self.errors.push(self.line);
-            }
+        self.advance();
+        let literal = Literal::String(word.clone());
+        self.add_token(TokenType::String, word, literal);
+    }
+
+    fn escape(&mut self) -> Result<char, String> {
+        match self.advance().unwrap() {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            '"' => Ok('"'),
+            '\\' => Ok('\\'),
+            '$' => Ok('$'),
+            'u' => self.unicode_escape(),
+            ch => Err(format!("Invalid escape sequence '\\{}'.", ch)),
+        }
+    }
 
-            _ => panic!(),
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        if self.chars.peek() != Some(&'{') {
+            return Err("Expect '{' after '\\u'.".into());
+        }
+        self.advance();
+
+        let mut digits = String::new();
+        while let Some(&ch) = self.chars.peek() {
+            if !ch.is_ascii_hexdigit() {
+                break;
+            }
+            digits.push(ch);
+            self.advance();
+        }
+        if self.chars.peek() != Some(&'}') {
+            return Err("Expect '}' after unicode escape.".into());
         }
+        self.advance();
+
+        let code_point = match digits.len() {
+            1..=6 => u32::from_str_radix(&digits, 16).ok(),
+            _ => None,
+        };
+        code_point
+            .and_then(char::from_u32)
+            .ok_or_else(|| format!("Invalid unicode escape '\\u{{{}}}'.", digits))
     }
 
     fn scan_next_token(&mut self) {
-        let ch = self.chars.next().unwrap_or(' ');
+        self.token_line = self.line;
+        self.token_column = self.column;
+        self.token_start = self.offset;
+        let ch = self.advance().unwrap_or(' ');
+        if let Some(depth) = self.interpolations.last_mut() {
+            match ch {
+                '{' => *depth += 1,
+                '}' if *depth == 0 => {
+                    self.interpolations.pop();
+                    return self.string();
+                }
+                '}' => *depth -= 1,
+                _ => (),
+            }
+        }
         if SINGLE_TOKEN_MAP.contains_key(&ch) {
             let token = SINGLE_TOKEN_MAP.get(&ch).unwrap();
-            return self.add_token(*token, &ch.to_string(), Literal::None);
+            return self.add_token(*token, ch.to_string(), Literal::None);
         }
         match ch {
             '"' => self.string(),
             '0'..='9' => self.number(ch),
             'a'..='z' | 'A'..='Z' | '_' => self.identifier(ch),
-            ' ' | '\t' | '\r' => (),
-            '\n' => self.line += 1,
+            ' ' | '\t' | '\r' | '\n' => (),
             '!' => {
                 let next_ch = self.chars.peek();
                 if next_ch == Some(&'=') {
+                    self.advance();
                     self.add_token(TokenType::BangEqual, "!=", Literal::None);
-                    self.chars.next();
                 } else {
-                    self.add_token(TokenType::Bang, &"!", Literal::None);
+                    self.add_token(TokenType::Bang, "!", Literal::None);
                 }
             }
             '=' => {
                 let next_ch = self.chars.peek();
                 if next_ch == Some(&'=') {
+                    self.advance();
                     self.add_token(TokenType::EqualEqual, "==", Literal::None);
-                    self.chars.next();
                 } else {
                     self.add_token(TokenType::Equal, "=", Literal::None);
                 }
@@ -205,8 +322,11 @@ impl<'a> Scanner<'a> {
             '<' => {
                 let next_ch = self.chars.peek();
                 if next_ch == Some(&'=') {
+                    self.advance();
                     self.add_token(TokenType::LessEqual, "<=", Literal::None);
-                    self.chars.next();
+                } else if next_ch == Some(&'<') {
+                    self.advance();
+                    self.add_token(TokenType::LessLess, "<<", Literal::None);
                 } else {
                     self.add_token(TokenType::Less, "<", Literal::None);
                 }
@@ -214,34 +334,95 @@ impl<'a> Scanner<'a> {
             '>' => {
                 let next_ch = self.chars.peek();
                 if next_ch == Some(&'=') {
+                    self.advance();
                     self.add_token(TokenType::GreaterEqual, ">=", Literal::None);
-                    self.chars.next();
+                } else if next_ch == Some(&'>') {
+                    self.advance();
+                    self.add_token(TokenType::GreaterGreater, ">>", Literal::None);
                 } else {
                     self.add_token(TokenType::Greater, ">", Literal::None);
                 }
             }
+            '+' => {
+                let next_ch = self.chars.peek();
+                if next_ch == Some(&'=') {
+                    self.advance();
+                    self.add_token(TokenType::PlusEqual, "+=", Literal::None);
+                } else if next_ch == Some(&'+') {
+                    self.advance();
+                    self.add_token(TokenType::PlusPlus, "++", Literal::None);
+                } else {
+                    self.add_token(TokenType::Plus, "+", Literal::None);
+                }
+            }
+            '-' => {
+                let next_ch = self.chars.peek();
+                if next_ch == Some(&'=') {
+                    self.advance();
+                    self.add_token(TokenType::MinusEqual, "-=", Literal::None);
+                } else if next_ch == Some(&'-') {
+                    self.advance();
+                    self.add_token(TokenType::MinusMinus, "--", Literal::None);
+                } else {
+                    self.add_token(TokenType::Minus, "-", Literal::None);
+                }
+            }
+            '*' => {
+                let next_ch = self.chars.peek();
+                if next_ch == Some(&'*') {
+                    self.advance();
+                    self.add_token(TokenType::StarStar, "**", Literal::None);
+                } else if next_ch == Some(&'=') {
+                    self.advance();
+                    self.add_token(TokenType::StarEqual, "*=", Literal::None);
+                } else {
+                    self.add_token(TokenType::Star, "*", Literal::None);
+                }
+            }
             '/' => {
                 let next_ch = self.chars.peek();
                 if next_ch == Some(&'/') {
-                    let mut next = self.chars.next();
-                    while next != Some('\n') && next != None {
-                        next = self.chars.next();
+                    while self.chars.peek().is_some() && self.chars.peek() != Some(&'\n') {
+                        self.advance();
                     }
+                } else if next_ch == Some(&'=') {
+                    self.advance();
+                    self.add_token(TokenType::SlashEqual, "/=", Literal::None);
                 } else {
                     self.add_token(TokenType::Slash, "/", Literal::None);
                 }
             }
-            char => {
-                println!("Unexpected char {:?} at line: {}", char, self.line);
-                self.errors.push(self.line);
-            }
+            char => self.error(format!("Unexpected character {:?}.", char)),
         }
     }
 
     fn add_token<S: AsRef<str>>(&mut self, token: TokenType, token_str: S, lit: Literal) {
-        let next_token = Token::new(token, token_str.as_ref().to_string(), lit, self.line as u32);
+        let span = Span::new(self.token_start, self.offset, self.token_column);
+        let next_token = Token::new(token, token_str.as_ref().to_string(), lit, self.line as u32)
+            .with_span(span);
         self.tokens.push(next_token);
     }
+
+    fn error<S: Into<String>>(&mut self, message: S) {
+        self.errors.push(ScanError {
+            message: message.into(),
+            line: self.token_line,
+            column: self.token_column,
+            span: Span::new(self.token_start, self.offset, self.token_column),
+        });
+    }
+
+    // For errors in the middle of a token, like a bad escape in a string. The
+    // position is the one the bad part started at, which can be on an earlier
+    // line than the scanner is by now.
+    fn error_at<S: Into<String>>(&mut self, message: S, line: usize, start: usize, column: usize) {
+        self.errors.push(ScanError {
+            message: message.into(),
+            line,
+            column,
+            span: Span::new(start, self.offset, column),
+        });
+    }
 }
 
 #[cfg(test)]
@@ -265,12 +446,14 @@ mod tests {
 
         let text = "\"string\";";
         let mut scanner = Scanner::new(text);
-        assert_eq!(tokens, *scanner.scan_text());
+        let (scanned, errors) = scanner.scan_text();
+        assert_eq!(tokens, scanned);
+        assert_eq!(errors, vec![]);
     }
 
     #[test]
     fn negation() {
-        let number = Token::new(TokenType::Number, "1".to_owned(), Literal::F64(1.0), 1);
+        let number = Token::new(TokenType::Number, "1".to_owned(), Literal::Int(1), 1);
         let operator = Token::new(TokenType::Bang, "!".to_owned(), Literal::None, 1);
 
         let semicolon = Token::new(TokenType::Semicolon, ";".to_owned(), Literal::None, 1);
@@ -279,13 +462,15 @@ mod tests {
 
         let text = "!1;";
         let mut scanner = Scanner::new(text);
-        assert_eq!(tokens, *scanner.scan_text());
+        let (scanned, errors) = scanner.scan_text();
+        assert_eq!(tokens, scanned);
+        assert_eq!(errors, vec![]);
     }
 
     #[test]
     fn addition() {
-        let first_number = Token::new(TokenType::Number, "1".to_owned(), Literal::F64(1.0), 1);
-        let second_number = Token::new(TokenType::Number, "2".to_owned(), Literal::F64(2.0), 1);
+        let first_number = Token::new(TokenType::Number, "1".to_owned(), Literal::Int(1), 1);
+        let second_number = Token::new(TokenType::Number, "2".to_owned(), Literal::Int(2), 1);
         let operator = Token::new(TokenType::Plus, "+".to_owned(), Literal::None, 1);
 
         let semicolon = Token::new(TokenType::Semicolon, ";".to_owned(), Literal::None, 1);
@@ -294,13 +479,15 @@ mod tests {
 
         let text = "1 + 2;";
         let mut scanner = Scanner::new(text);
-        assert_eq!(tokens, *scanner.scan_text());
+        let (scanned, errors) = scanner.scan_text();
+        assert_eq!(tokens, scanned);
+        assert_eq!(errors, vec![]);
     }
 
     #[test]
     fn equality() {
-        let first_number = Token::new(TokenType::Number, "1".to_owned(), Literal::F64(1.0), 1);
-        let second_number = Token::new(TokenType::Number, "1".to_owned(), Literal::F64(1.0), 1);
+        let first_number = Token::new(TokenType::Number, "1".to_owned(), Literal::Int(1), 1);
+        let second_number = Token::new(TokenType::Number, "1".to_owned(), Literal::Int(1), 1);
         let operator = Token::new(TokenType::EqualEqual, "==".to_owned(), Literal::None, 1);
 
         let semicolon = Token::new(TokenType::Semicolon, ";".to_owned(), Literal::None, 1);
@@ -309,13 +496,15 @@ mod tests {
 
         let text = "1 == 1;";
         let mut scanner = Scanner::new(text);
-        assert_eq!(tokens, *scanner.scan_text());
+        let (scanned, errors) = scanner.scan_text();
+        assert_eq!(tokens, scanned);
+        assert_eq!(errors, vec![]);
     }
 
     #[test]
     fn comparison() {
-        let first_number = Token::new(TokenType::Number, "1".to_owned(), Literal::F64(1.0), 1);
-        let second_number = Token::new(TokenType::Number, "2".to_owned(), Literal::F64(2.0), 1);
+        let first_number = Token::new(TokenType::Number, "1".to_owned(), Literal::Int(1), 1);
+        let second_number = Token::new(TokenType::Number, "2".to_owned(), Literal::Int(2), 1);
         let operator = Token::new(TokenType::Greater, ">".to_owned(), Literal::None, 1);
         let semicolon = Token::new(TokenType::Semicolon, ";".to_owned(), Literal::None, 1);
 
@@ -324,21 +513,25 @@ mod tests {
 
         let text = "1 > 2;";
         let mut scanner = Scanner::new(text);
-        assert_eq!(tokens, *scanner.scan_text());
+        let (scanned, errors) = scanner.scan_text();
+        assert_eq!(tokens, scanned);
+        assert_eq!(errors, vec![]);
     }
 
     #[test]
     fn multiplication() {
-        let first_number = Token::new(TokenType::Number, "1".to_owned(), Literal::F64(1.0), 1);
+        let first_number = Token::new(TokenType::Number, "1".to_owned(), Literal::Int(1), 1);
         let operator = Token::new(TokenType::Star, "*".to_owned(), Literal::None, 1);
-        let second_number = Token::new(TokenType::Number, "1".to_owned(), Literal::F64(1.0), 1);
+        let second_number = Token::new(TokenType::Number, "1".to_owned(), Literal::Int(1), 1);
         let semicolon = Token::new(TokenType::Semicolon, ";".to_owned(), Literal::None, 1);
 
         let eof = Token::new(TokenType::EOF, "".to_owned(), Literal::None, 1);
         let tokens = vec![first_number, operator, second_number, semicolon, eof];
         let text = "1 * 1;";
         let mut scanner = Scanner::new(text);
-        assert_eq!(tokens, *scanner.scan_text());
+        let (scanned, errors) = scanner.scan_text();
+        assert_eq!(tokens, scanned);
+        assert_eq!(errors, vec![]);
     }
 
     #[test]
@@ -348,13 +541,13 @@ mod tests {
         let left_paren = Token::new(TokenType::LeftParen, "(".to_owned(), Literal::None, 1);
         let variable = Token::new(TokenType::Identifier, "a".to_owned(), Literal::None, 1);
         let greater = Token::new(TokenType::Less, "<".to_owned(), Literal::None, 1);
-        let two = Token::new(TokenType::Number, "2".to_owned(), Literal::F64(2.0), 1);
+        let two = Token::new(TokenType::Number, "2".to_owned(), Literal::Int(2), 1);
         let right_paren = Token::new(TokenType::RightParen, ")".to_owned(), Literal::None, 1);
 
         let left_bracket = Token::new(TokenType::LeftBrace, "{".to_owned(), Literal::None, 1);
         let plus_sign = Token::new(TokenType::Plus, "+".to_owned(), Literal::None, 1);
         let equal_sign = Token::new(TokenType::Equal, "=".to_owned(), Literal::None, 1);
-        let one = Token::new(TokenType::Number, "1".to_owned(), Literal::F64(1.0), 1);
+        let one = Token::new(TokenType::Number, "1".to_owned(), Literal::Int(1), 1);
         let right_bracket = Token::new(TokenType::RightBrace, "}".to_owned(), Literal::None, 1);
         let semicolon = Token::new(TokenType::Semicolon, ";".to_owned(), Literal::None, 1);
 
@@ -379,6 +572,221 @@ mod tests {
 
         let text = "while(a < 2) { a = a + 1; }";
         let mut scanner = Scanner::new(text);
-        assert_eq!(tokens, *scanner.scan_text());
+        let (scanned, errors) = scanner.scan_text();
+        assert_eq!(tokens, scanned);
+        assert_eq!(errors, vec![]);
+    }
+
+    #[test]
+    fn errors_have_line_and_column() {
+        let text = "var a = 1;\nvar b = @;\n\"open";
+        let mut scanner = Scanner::new(text);
+        let (_, errors) = scanner.scan_text();
+        assert_eq!(
+            errors,
+            vec![
+                ScanError {
+                    message: "Unexpected character '@'.".into(),
+                    line: 2,
+                    column: 9,
+                    span: Span::new(19, 20, 9),
+                },
+                ScanError {
+                    message: "Unterminated string.".into(),
+                    line: 3,
+                    column: 1,
+                    span: Span::new(22, 27, 1),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn tokens_without_separating_spaces() {
+        let identifier = Token::new(TokenType::Identifier, "x1".to_owned(), Literal::None, 1);
+        let less = Token::new(TokenType::Less, "<".to_owned(), Literal::None, 1);
+        let number = Token::new(TokenType::Number, "2.5".to_owned(), Literal::F64(2.5), 1);
+        let eof = Token::new(TokenType::EOF, "".to_owned(), Literal::None, 3);
+        let tokens = vec![identifier, less, number, eof];
+
+        let text = "x1<2.5 // comment\n\n";
+        let mut scanner = Scanner::new(text);
+        let (scanned, errors) = scanner.scan_text();
+        assert_eq!(tokens, scanned);
+        assert_eq!(errors, vec![]);
+    }
+
+    #[test]
+    fn brackets() {
+        let tokens = vec![
+            Token::new(TokenType::Identifier, "xs".to_owned(), Literal::None, 1),
+            Token::new(TokenType::LeftBracket, "[".to_owned(), Literal::None, 1),
+            Token::new(TokenType::Number, "0".to_owned(), Literal::Int(0), 1),
+            Token::new(TokenType::RightBracket, "]".to_owned(), Literal::None, 1),
+            Token::new(TokenType::EOF, "".to_owned(), Literal::None, 1),
+        ];
+
+        let mut scanner = Scanner::new("xs[0]");
+        let (scanned, errors) = scanner.scan_text();
+        assert_eq!(tokens, scanned);
+        assert_eq!(errors, vec![]);
+    }
+
+    #[test]
+    fn string_escapes() {
+        let text = r#""a\n\t\"\\\u{e9}\$""#;
+        let mut scanner = Scanner::new(text);
+        let (scanned, errors) = scanner.scan_text();
+        assert_eq!(errors, vec![]);
+        assert_eq!(scanned[0].literal, Literal::String("a\n\t\"\\é$".into()));
+    }
+
+    #[test]
+    fn bad_escapes_are_errors() {
+        let text = r#""\q \u{110000} \u41""#;
+        let mut scanner = Scanner::new(text);
+        let (_, errors) = scanner.scan_text();
+        let messages: Vec<(&str, usize)> = errors
+            .iter()
+            .map(|error| (error.message.as_str(), error.column))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                ("Invalid escape sequence '\\q'.", 2),
+                ("Invalid unicode escape '\\u{110000}'.", 5),
+                ("Expect '{' after '\\u'.", 16)
+            ]
+        );
+
+        let mut scanner = Scanner::new("\"a\\\n\"");
+        let (_, errors) = scanner.scan_text();
+        let positions: Vec<(&str, usize, usize)> = errors
+            .iter()
+            .map(|error| (error.message.as_str(), error.line, error.column))
+            .collect();
+        assert_eq!(positions, vec![("Invalid escape sequence '\\\n'.", 1, 3)]);
+    }
+
+    #[test]
+    fn string_interpolation() {
+        let text = r#""a${b}c${ {"d": 1}["d"] }""#;
+        let mut scanner = Scanner::new(text);
+        let (scanned, errors) = scanner.scan_text();
+        assert_eq!(errors, vec![]);
+        let types: Vec<TokenType> = scanned.iter().map(|token| token.t_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::Interpolation,
+                TokenType::Identifier,
+                TokenType::Interpolation,
+                TokenType::LeftBrace,
+                TokenType::String,
+                TokenType::Colon,
+                TokenType::Number,
+                TokenType::RightBrace,
+                TokenType::LeftBracket,
+                TokenType::String,
+                TokenType::RightBracket,
+                TokenType::String,
+                TokenType::EOF,
+            ]
+        );
+        assert_eq!(scanned[0].literal, Literal::String("a".into()));
+        assert_eq!(scanned[2].literal, Literal::String("c".into()));
+        assert_eq!(scanned[11].literal, Literal::String("".into()));
+
+        let mut scanner = Scanner::new("\"a${b");
+        let (_, errors) = scanner.scan_text();
+        assert_eq!(errors[0].message, "Unterminated string interpolation.");
+    }
+
+    #[test]
+    fn arithmetic_and_bitwise_operators() {
+        let mut scanner = Scanner::new("% * ** & | ^ ~ << >> <= >=");
+        let (scanned, errors) = scanner.scan_text();
+        assert_eq!(errors, vec![]);
+        let types: Vec<TokenType> = scanned.iter().map(|token| token.t_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::Percent,
+                TokenType::Star,
+                TokenType::StarStar,
+                TokenType::Ampersand,
+                TokenType::Pipe,
+                TokenType::Caret,
+                TokenType::Tilde,
+                TokenType::LessLess,
+                TokenType::GreaterGreater,
+                TokenType::LessEqual,
+                TokenType::GreaterEqual,
+                TokenType::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn compound_assignment_operators() {
+        let mut scanner = Scanner::new("+= -= *= /= ++ -- + - // comment");
+        let (scanned, errors) = scanner.scan_text();
+        assert_eq!(errors, vec![]);
+        let types: Vec<TokenType> = scanned.iter().map(|token| token.t_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::PlusEqual,
+                TokenType::MinusEqual,
+                TokenType::StarEqual,
+                TokenType::SlashEqual,
+                TokenType::PlusPlus,
+                TokenType::MinusMinus,
+                TokenType::Plus,
+                TokenType::Minus,
+                TokenType::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn integer_and_float_literals() {
+        let mut scanner = Scanner::new("12 1.5 3.0 9223372036854775808");
+        let (scanned, errors) = scanner.scan_text();
+        let literals: Vec<&Literal> = scanned.iter().map(|token| &token.literal).collect();
+        assert_eq!(
+            literals,
+            vec![
+                &Literal::Int(12),
+                &Literal::F64(1.5),
+                &Literal::F64(3.0),
+                &Literal::None
+            ]
+        );
+        assert_eq!(
+            errors[0].message,
+            "Integer literal 9223372036854775808 is too large."
+        );
+    }
+
+    #[test]
+    fn token_spans() {
+        let text = "var ab = \"é\" != 1;";
+        let mut scanner = Scanner::new(text);
+        let (scanned, _) = scanner.scan_text();
+        let spans: Vec<Span> = scanned.iter().map(|token| token.span).collect();
+        assert_eq!(
+            spans,
+            vec![
+                Span::new(0, 3, 1),
+                Span::new(4, 6, 5),
+                Span::new(7, 8, 8),
+                Span::new(9, 13, 10),
+                Span::new(14, 16, 14),
+                Span::new(17, 18, 17),
+                Span::new(18, 19, 18),
+                Span::new(19, 19, 19),
+            ]
+        );
     }
 }
@@ -1,4 +1,6 @@
-use super::expr::Expr;
+use super::expr::{self, Expr};
+use super::token::Token;
+use std::rc::Rc;
 
 #[derive(PartialEq, Clone, Debug)]
 pub enum Stmt {
@@ -8,6 +10,15 @@ pub enum Stmt {
     Block(Block),
     If(IfStmt),
     While(While),
+    Function(Rc<Function>),
+    Return(Return),
+    Class(Class),
+    Break(Break),
+    Continue(Continue),
+    Defer(Defer),
+    Throw(Throw),
+    Import(Import),
+    Try(Try),
 }
 
 #[derive(PartialEq, Clone, Debug)]
@@ -18,13 +29,16 @@ pub struct Block {
 #[derive(PartialEq, Clone, Debug)]
 pub struct Var {
     pub value: Expr,
-    pub name: String,
+    pub name: Token,
 }
 
+// `increment` is only set for desugared `for` loops, it runs after every
+// iteration including ones cut short by `continue`.
 #[derive(PartialEq, Clone, Debug)]
 pub struct While {
     pub condition: Expr,
     pub body: Box<Stmt>,
+    pub increment: Option<Expr>,
 }
 
 #[derive(PartialEq, Clone, Debug)]
@@ -33,3 +47,68 @@ pub struct IfStmt {
     pub false_branch: Box<Option<Stmt>>,
     pub condition: Expr,
 }
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Function {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Vec<Stmt>,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Return {
+    pub keyword: Token,
+    pub value: Option<Expr>,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Class {
+    pub name: Token,
+    pub superclass: Option<expr::Var>,
+    pub methods: Vec<Rc<Function>>,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Break {
+    pub keyword: Token,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Continue {
+    pub keyword: Token,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Defer {
+    pub keyword: Token,
+    pub body: Box<Stmt>,
+}
+
+// `name` is the path's file stem unless the import has an `as`.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Import {
+    pub keyword: Token,
+    pub path: Token,
+    pub name: Token,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Throw {
+    pub keyword: Token,
+    pub value: Expr,
+}
+
+// At least one of `catch` and `finally` is always there.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Try {
+    pub keyword: Token,
+    pub body: Vec<Stmt>,
+    pub catch: Option<Catch>,
+    pub finally: Option<Vec<Stmt>>,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Catch {
+    pub name: Token,
+    pub body: Vec<Stmt>,
+}
@@ -0,0 +1,140 @@
+use super::interpreter::interpreter::Interpreter;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(PartialEq, Debug)]
+pub struct Failure {
+    pub file: PathBuf,
+    pub line: Option<u32>,
+    pub message: String,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.file.display(), line, self.message),
+            None => write!(f, "{}: {}", self.file.display(), self.message),
+        }
+    }
+}
+
+// `passed` and `failed` count test files, a file fails when anything in it
+// does. `assertions` counts the assertions that held across all of them.
+#[derive(PartialEq, Debug, Default)]
+pub struct Report {
+    pub passed: usize,
+    pub failed: usize,
+    pub assertions: usize,
+    pub failures: Vec<Failure>,
+}
+
+// Every `*_test.lox` file under `root`, sorted so runs are repeatable.
+// Symlinked directories aren't followed, one pointing back up the tree would
+// make the search go on forever.
+pub fn find_tests(root: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut tests = vec![];
+    for entry in fs::read_dir(root)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            tests.extend(find_tests(&path)?);
+        } else if path.to_string_lossy().ends_with("_test.lox") {
+            tests.push(path);
+        }
+    }
+    tests.sort();
+    Ok(tests)
+}
+
+// Top level statements run one after the other, a failed assertion or any
+// other runtime error is recorded and the next statement still runs.
+pub fn run_test(interpreter: Interpreter, path: &Path, report: &mut Report) {
+    let failures = report.failures.len();
+    run_statements(interpreter, path, report);
+    if report.failures.len() == failures {
+        report.passed += 1;
+    } else {
+        report.failed += 1;
+    }
+}
+
+fn run_statements(mut interpreter: Interpreter, path: &Path, report: &mut Report) {
+    let failure = |line, message| Failure {
+        file: path.to_path_buf(),
+        line,
+        message,
+    };
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(error) => return report.failures.push(failure(None, error.to_string())),
+    };
+    let statements = match Interpreter::parse_module(&source) {
+        Ok(statements) => statements,
+        Err(message) => return report.failures.push(failure(None, message)),
+    };
+
+    interpreter.set_script(path);
+    for stmt in &statements {
+        if let Err(error) = interpreter.evaluate_node(stmt) {
+            let mut failure = failure(Some(error.line()), error.message);
+            if let Some(file) = error.file {
+                failure.file = file.to_path_buf();
+            }
+            report.failures.push(failure);
+        }
+    }
+    report.assertions += interpreter.assertions_passed();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn failures_are_reported_and_the_file_keeps_running() {
+        let dir = std::env::temp_dir().join(format!("rlox-testing-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        let math = dir.join("nested/math_test.lox");
+        fs::write(
+            &math,
+            "assertEqual(1 + 1, 2);\nassertEqual([1, {\"a\": 2}], [1, {\"a\": 2}]);\n\
+             assertEqual(2 * 2, 5);\nassert(1 < 2, \"ordered\");\n\
+             assert(false, \"always fails\");\nprint undefined;",
+        )
+        .unwrap();
+        let broken = dir.join("broken_test.lox");
+        fs::write(&broken, "var = 1;").unwrap();
+        let passing = dir.join("passing_test.lox");
+        fs::write(&passing, "assert(true, \"holds\");").unwrap();
+        fs::write(dir.join("helper.lox"), "assert(false, \"not a test\");").unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("nested/loop")).unwrap();
+
+        let tests = find_tests(&dir).unwrap();
+        assert_eq!(tests, vec![broken.clone(), math.clone(), passing]);
+
+        let mut report = Report::default();
+        for test in &tests {
+            run_test(Interpreter::new(), test, &mut report);
+        }
+        assert_eq!((report.passed, report.failed), (1, 2));
+        assert_eq!(report.assertions, 4);
+        let failures: Vec<String> = report.failures.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            failures,
+            vec![
+                format!(
+                    "{}: [line 1] Error at '=': Expect variable name.",
+                    broken.display()
+                ),
+                format!("{}:3: Expected 5 but got 4.", math.display()),
+                format!("{}:5: always fails", math.display()),
+                format!("{}:6: Undefined variable 'undefined'.", math.display()),
+            ]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,5 +1,6 @@
 use std::fmt;
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(PartialEq, Debug, Copy, Clone)]
 pub enum TokenType {
     // Single-character tokens.
@@ -7,6 +8,10 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
+    Colon,
+    Question,
     Comma,
     Dot,
     Minus,
@@ -14,6 +19,11 @@ pub enum TokenType {
     Semicolon,
     Slash,
     Star,
+    Percent,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
 
     // One or two character tokens.
     Bang,
@@ -24,27 +34,46 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    LessLess,
+    GreaterGreater,
+    StarStar,
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    SlashEqual,
+    PlusPlus,
+    MinusMinus,
 
     // Literals.
     Identifier,
     String,
+    // The part of a string before a `${`.
+    Interpolation,
     Number,
 
     // Keywords.
     And,
+    Break,
+    Catch,
     Class,
+    Continue,
+    Defer,
     Else,
     False,
+    Finally,
     Fun,
     For,
     If,
+    Import,
     Nil,
     Or,
     Print,
     Return,
     Super,
     This,
+    Throw,
     True,
+    Try,
     Var,
     While,
 
@@ -54,18 +83,53 @@ pub enum TokenType {
 #[derive(PartialEq, Debug, Clone)]
 pub enum Literal {
     String(String),
+    Int(i64),
     F64(f64),
     Boolean(bool),
     Nil,
     None,
 }
 
-#[derive(PartialEq, Debug, Clone)]
+// Byte offsets into the source, `column` is the character column of `start`.
+#[derive(PartialEq, Debug, Copy, Clone, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, column: usize) -> Self {
+        Span { start, end, column }
+    }
+
+    pub fn merge(self, other: Span) -> Span {
+        if self == Span::default() {
+            return other;
+        }
+        if other == Span::default() {
+            return self;
+        }
+        let column = if self.start <= other.start {
+            self.column
+        } else {
+            other.column
+        };
+        Span {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+            column,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Token {
     pub t_type: TokenType,
     pub lexeme: String,
     pub literal: Literal,
     pub line: u32,
+    pub span: Span,
 }
 
 impl Token {
@@ -75,6 +139,7 @@ impl Token {
             lexeme,
             literal,
             line,
+            span: Span::default(),
         }
     }
 
@@ -84,8 +149,25 @@ impl Token {
             lexeme: "".to_owned(),
             literal: Literal::None,
             line,
+            span: Span::default(),
         }
     }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = span;
+        self
+    }
+}
+
+// Where a token sits in the source doesn't change what it is, so spans are
+// left out of the comparison.
+impl PartialEq for Token {
+    fn eq(&self, other: &Self) -> bool {
+        self.t_type == other.t_type
+            && self.lexeme == other.lexeme
+            && self.literal == other.literal
+            && self.line == other.line
+    }
 }
 
 impl fmt::Display for Token {
@@ -1,19 +1,51 @@
 mod lox;
+use lox::interpreter::interpreter::STACK_SIZE;
 use lox::Lox;
-use std::{env, path};
+use std::{env, path, process, thread};
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let runner = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run)
+        .expect("Can't start the interpreter thread");
+    if runner.join().is_err() {
+        process::exit(101);
+    }
+}
+
+fn run() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
     let mut lox = Lox::new();
-    match args.len() {
-        1 => lox.prompt(),
-        2 => {
-            let path = path::PathBuf::from(&args[1]);
-            lox.runfile(path);
-        }
-        _ => {
-            println!("Usage rlox [script]");
-            std::process::exit(1);
+    if let Some(seed) = take_option(&mut args, "--seed") {
+        match seed.parse() {
+            Ok(seed) => lox.seed(seed),
+            Err(_) => usage(),
         }
     }
+    if let Some(root) = take_option(&mut args, "--sandbox") {
+        lox.sandbox(path::PathBuf::from(root));
+    }
+    match args.as_slice() {
+        [] => lox.prompt(),
+        [command] if command == "test" => lox.test(path::PathBuf::from(".")),
+        [command, root] if command == "test" => lox.test(path::PathBuf::from(root)),
+        [script] => lox.runfile(path::PathBuf::from(script)),
+        _ => usage(),
+    }
+}
+
+// Removes `name` and the value after it from the arguments.
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let position = args.iter().position(|arg| arg == name)?;
+    if position + 1 == args.len() {
+        usage();
+    }
+    let value = args.remove(position + 1);
+    args.remove(position);
+    Some(value)
+}
+
+fn usage() -> ! {
+    println!("Usage rlox [--seed n] [--sandbox dir] [script | test [dir]]");
+    std::process::exit(1);
 }